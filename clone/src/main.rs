@@ -1,20 +1,43 @@
 // clone
 
-use std::env;
+use std::{env, fmt, fs, thread};
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Command, Output, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use eyre::{Result, eyre, WrapErr};
-use log::{debug, warn, error};
-use env_logger;
+use glob::{MatchOptions, Pattern};
+use log::{debug, info, warn, error};
 use ini::ini;
+use serde_json::json;
 
 const REMOTE_URLS: [&str; 2] = [
     "ssh://git@github.com",
     "https://github.com",
 ];
 
+const EXAMPLE_CONFIG: &str = "\
+# clone.cfg: per-org settings, looked up by the org in the repospec.
+# Orgs without their own [org.<name>] section use [org.default].
+
+[org.default]
+# ssh or https: which transport to try first
+prefer = ssh
+# private key used for SSH clones
+# sshkey = ~/.ssh/id_ed25519
+# shell command run in each new checkout
+# postclone = make setup
+
+# [org.myorg]
+# sshkey = ~/.ssh/id_myorg
+# prefer = https
+";
+
 mod built_info {
     include!(concat!(env!("OUT_DIR"), "/git_describe.rs"));
 }
@@ -25,8 +48,8 @@ mod built_info {
 #[command(author = "Scott A. Idler <scott.a.idler@gmail.com>")]
 #[command(arg_required_else_help = true)]
 struct Cli {
-    #[arg(help = "repospec schema is remote?reponame", required = true)]
-    repospec: String,
+    #[arg(help = "repospec schema is remote?reponame; globs like 'org/*' clone every matching repo", required_unless_present_any = ["org", "from_manifest", "init_config"])]
+    repospec: Option<String>,
 
     #[arg(help = "revision to check out", default_value = "HEAD")]
     revision: String,
@@ -43,109 +66,665 @@ struct Cli {
     #[arg(long, help = "turn on versioning; checkout in reponame/commit rather than reponame")]
     versioning: bool,
 
+    #[arg(long, value_name = "N", requires = "versioning", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..), help = "after a versioned clone, keep the checked-out commit directory plus the newest of the rest, N in total, ordered by commit time")]
+    keep: Option<usize>,
+
+    #[arg(long, value_name = "FILTER", help = "partial clone filter passed as --filter, e.g. blob:none or tree:0")]
+    partial: Option<String>,
+
+    #[arg(long, value_enum, help = "transport to try first; the other is the fallback [default: prefer in clone.cfg, else ssh]")]
+    prefer: Option<Prefer>,
+
+    #[arg(long, help = "clone every repo under this GitHub org (uses ls-github-repos); a repospec glob like 'api-*' narrows the list")]
+    org: Option<String>,
+
+    #[arg(long, help = "when cloning many repos, skip ones a previous interrupted run already completed")]
+    resume: bool,
+
+    #[arg(long, help = "when cloning many repos, stop at the first failure instead of continuing")]
+    fail_fast: bool,
+
+    #[arg(long, help = "recreate a layout from a manifest of 'path slug [url [branch]]' lines, paths relative to --clonepath", conflicts_with_all = ["repospec", "org"])]
+    from_manifest: Option<PathBuf>,
+
+    #[arg(long, help = "shell command to run in the checkout after cloning; defaults to postclone in clone.cfg")]
+    post_clone: Option<String>,
+
+    #[arg(long, help = "also run the post-clone command when updating an existing checkout")]
+    hook_on_update: bool,
+
+    #[arg(long, help = "report post-clone command failures without failing the clone")]
+    ignore_hook_errors: bool,
+
+    #[arg(long, help = "when updating an existing checkout, prune remote-tracking refs deleted upstream")]
+    prune: bool,
+
+    #[arg(long, help = "update existing checkouts with fetch + reset --hard + clean -xfd, discarding local changes")]
+    hard_reset: bool,
+
+    #[arg(long, value_name = "SECS", help = "kill network git operations (clone, fetch, pull, ls-remote) after this long; batches skip the repo")]
+    timeout: Option<u64>,
+
+    #[arg(long, help = "print a JSON summary per repo instead of the repospec")]
+    json: bool,
+
+    #[arg(long, help = "require the checkout's parent directories to exist instead of creating them")]
+    no_parents: bool,
+
+    #[arg(long, help = "after cloning, run git fsck and check the fetched origin/HEAD matches the remote's HEAD, removing the checkout if either fails")]
+    verify: bool,
+
+    #[arg(long, help = "write a commented example clone.cfg if none exists, print its path and exit")]
+    init_config: bool,
+
+    #[arg(long, value_name = "PATH", help = "per-org settings file [default: $CLONE_CFG, else ~/.config/clone/clone.cfg]")]
+    config: Option<PathBuf>,
+
+    #[arg(long, value_name = "DIR", help = "where batch progress for --resume is kept [default: $XDG_CACHE_HOME/clone]")]
+    state_dir: Option<PathBuf>,
+
     #[arg(long, help = "turn on verbose output")]
     verbose: bool,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum Prefer {
+    /// Try --remote first, then HTTPS
+    Ssh,
+    /// Try HTTPS first, for networks that block SSH egress
+    Https,
+}
+
+#[derive(Debug)]
+struct TimedOut(Duration);
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timed out after {}s", self.0.as_secs())
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+// one repo to clone; manifest entries may pin the checkout path and the URL to clone from
+#[derive(Debug, PartialEq)]
+struct CloneEntry {
+    repospec: String,
+    revision: String,
+    path: Option<PathBuf>,
+    url: Option<String>,
+}
+
+impl CloneEntry {
+    fn new(repospec: &str, revision: &str) -> Self {
+        CloneEntry { repospec: repospec.to_string(), revision: revision.to_string(), path: None, url: None }
+    }
+
+    fn target(&self, clonepath: &str) -> PathBuf {
+        PathBuf::from(clonepath).join(self.path.as_deref().unwrap_or(Path::new(&self.repospec)))
+    }
+}
+
+#[derive(Debug)]
+struct CloneReport {
+    repospec: String,
+    action: &'static str,
+    path: PathBuf,
+    revision: Option<String>,
+    remote: Option<String>,
+}
+
+impl CloneReport {
+    fn print(&self, json: bool) {
+        if json {
+            println!("{}", json!({
+                "repospec": self.repospec,
+                "action": self.action,
+                "path": self.path,
+                "revision": self.revision,
+                "remote": self.remote,
+            }));
+        } else if self.action != "skipped" {
+            println!("{}", self.repospec);
+        }
+    }
+}
+
 fn main() -> Result<()> {
     env_logger::init();
 
     let cli = Cli::parse();
 
-    let full_clone_path = PathBuf::from(&cli.clonepath).join(&cli.repospec);
+    if cli.init_config {
+        return init_config(&cli);
+    }
+    ensure_installed("git")?;
+
+    if let Some(manifest) = &cli.from_manifest {
+        let entries = read_manifest(manifest)?;
+        info!("Cloning {} repos from {}", entries.len(), manifest.display());
+        return clone_all(&cli, &entries);
+    }
+
+    match expansion_pattern(&cli) {
+        Some((org, pattern)) => clone_many(&cli, &org, &pattern),
+        None => {
+            let repospec = cli.repospec.clone().ok_or_else(|| eyre!("repospec is required"))?;
+            clone_one(&cli, &CloneEntry::new(&repospec, &cli.revision))
+        }
+    }
+}
+
+fn clone_one(cli: &Cli, entry: &CloneEntry) -> Result<()> {
+    let repospec = entry.repospec.as_str();
+    // a versioned checkout lives under its commit, so resolve that before deciding whether to clone
+    let (full_clone_path, revision) = if cli.versioning {
+        let repo_url = entry.url.clone().unwrap_or_else(|| remote_repo_url(&cli.remote, repospec));
+        let sha = fetch_revision_sha(&repo_url, cli.timeout.map(Duration::from_secs))?;
+        (entry.target(&cli.clonepath).join(&sha), sha)
+    } else {
+        (entry.target(&cli.clonepath), entry.revision.clone())
+    };
+    let revision = revision.as_str();
 
-    if full_clone_path.exists() && full_clone_path.read_dir()?.next().is_some() {
-        update_existing_repo(&full_clone_path, &cli.revision)?
+    let existing = full_clone_path.exists() && full_clone_path.read_dir()?.next().is_some();
+    let report = if existing && cli.versioning {
+        debug!("{} is already checked out at {}", repospec, full_clone_path.display());
+        CloneReport {
+            repospec: repospec.to_string(),
+            action: "unchanged",
+            revision: Some(revision.to_string()),
+            path: full_clone_path,
+            remote: None,
+        }
+    } else if existing {
+        update_existing_repo(cli, &full_clone_path, revision)?;
+        if cli.hook_on_update {
+            run_post_clone(cli, repospec, &full_clone_path)?;
+        }
+        CloneReport {
+            repospec: repospec.to_string(),
+            action: "updated",
+            revision: head_sha(&full_clone_path),
+            path: full_clone_path,
+            remote: None,
+        }
     } else {
-        clone_new_repo(&cli)?
+        let remote = clone_new_repo(cli, repospec, revision, &full_clone_path, entry.url.as_deref())?;
+        if cli.verify {
+            let ssh_key = find_ssh_key_for_org(cli, repospec)?;
+            // a checkout that fails verification is removed so the next run clones it afresh
+            if let Err(e) = verify_clone(&full_clone_path, ssh_key.as_deref(), cli.timeout.map(Duration::from_secs)) {
+                fs::remove_dir_all(&full_clone_path)
+                    .wrap_err_with(|| format!("Failed to remove unverified checkout {}", full_clone_path.display()))?;
+                return Err(e.wrap_err(format!("Verification failed for {}", repospec)));
+            }
+        }
+        run_post_clone(cli, repospec, &full_clone_path)?;
+        CloneReport {
+            repospec: repospec.to_string(),
+            action: "cloned",
+            revision: head_sha(&full_clone_path),
+            path: full_clone_path,
+            remote: Some(remote),
+        }
+    };
+
+    if let (true, Some(keep), Some(repo_dir)) = (cli.versioning, cli.keep, report.path.parent()) {
+        for pruned in prune_versions(repo_dir, keep, &report.path)? {
+            eprintln!("Pruned {}", pruned.display());
+        }
     }
 
-    println!("{}", cli.repospec);
+    report.print(cli.json);
 
     Ok(())
 }
 
-fn update_existing_repo(full_clone_path: &Path, revision: &str) -> Result<()> {
-    env::set_current_dir(full_clone_path)
-        .wrap_err("Failed to set current directory")?;
-    Command::new("git")
-        .args(["checkout", revision])
-        .stdout(Stdio::null())
-        .status()
-        .wrap_err("Failed to checkout the specified revision")?;
+// a stale or corrupt --reference mirror can leave a clone that only fails much later
+fn verify_clone(checkout_path: &Path, ssh_key: Option<&str>, timeout: Option<Duration>) -> Result<()> {
+    git_output(checkout_path, &["fsck", "--no-progress"])?;
 
-    Command::new("git")
-        .args(["pull"])
-        .stdout(Stdio::null())
-        .status()
-        .wrap_err("Failed to pull the latest changes")?;
+    let mut ls_remote = Command::new("git");
+    ls_remote.args(["ls-remote", "origin", "HEAD"])
+        .current_dir(checkout_path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(key) = ssh_key {
+        ls_remote.env("GIT_SSH_COMMAND", format!("/usr/bin/ssh -i {}", key));
+    }
+    let output = run_bounded(&mut ls_remote, timeout).wrap_err("Failed to execute ls-remote")?;
+    if !output.status.success() {
+        return Err(eyre!("git ls-remote failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    let remote_head = String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .next()
+        .map(str::to_string)
+        .ok_or_else(|| eyre!("Remote did not report a HEAD"))?;
+    let local_head = git_output(checkout_path, &["rev-parse", "refs/remotes/origin/HEAD"])?;
+
+    if local_head != remote_head {
+        return Err(eyre!("origin/HEAD is {} but the remote HEAD is {}", local_head, remote_head));
+    }
+    debug!("Verified {} at {}", checkout_path.display(), local_head);
 
     Ok(())
 }
 
-fn clone_new_repo(cli: &Cli) -> Result<()> {
-    let revision = if cli.versioning {
-        fetch_revision_sha(&cli.remote, &cli.repospec, cli.verbose)?
+fn head_sha(path: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(path)
+        .output()
+        .ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn clone_many(cli: &Cli, org: &str, pattern: &str) -> Result<()> {
+    let entries: Vec<CloneEntry> = matching_repospecs(list_org_repos(org)?, pattern)?
+        .into_iter()
+        .map(|repospec| CloneEntry::new(&repospec, &cli.revision))
+        .collect();
+
+    if entries.is_empty() {
+        return Err(eyre!("No repos under {} matched {}", org, pattern));
+    }
+    info!("Cloning {} repos matching {}", entries.len(), pattern);
+
+    clone_all(cli, &entries)
+}
+
+// GitHub names are case-insensitive, so 'api-*' must also pick up 'API-Gateway'
+fn matching_repospecs(repospecs: Vec<String>, pattern: &str) -> Result<Vec<String>> {
+    let glob = Pattern::new(pattern).wrap_err_with(|| format!("Invalid repospec glob: {}", pattern))?;
+    let options = MatchOptions { case_sensitive: false, ..MatchOptions::default() };
+    Ok(repospecs.into_iter().filter(|repospec| glob.matches_with(repospec, options)).collect())
+}
+
+fn clone_all(cli: &Cli, entries: &[CloneEntry]) -> Result<()> {
+    let state_path = batch_state_path(cli, entries)?;
+    let completed: HashSet<String> = if cli.resume {
+        fs::read_to_string(&state_path)
+            .map(|state| state.lines().map(str::to_string).collect())
+            .unwrap_or_default()
     } else {
-        cli.revision.clone()
+        HashSet::new()
+    };
+    if !completed.is_empty() {
+        info!("Resuming from {}: {} repos already done", state_path.display(), completed.len());
+    }
+
+    if let Some(parent) = state_path.parent() {
+        fs::create_dir_all(parent).wrap_err("Failed to create batch state directory")?;
+    }
+    let mut state = OpenOptions::new()
+        .create(true)
+        .append(cli.resume)
+        .write(true)
+        .truncate(!cli.resume)
+        .open(&state_path)
+        .wrap_err_with(|| format!("Failed to open batch state file {}", state_path.display()))?;
+
+    let mut failures = 0;
+    let mut timed_out = 0;
+    for entry in entries {
+        let repospec = &entry.repospec;
+        if completed.contains(repospec) {
+            debug!("Skipping {}, completed in a previous run", repospec);
+            CloneReport {
+                repospec: repospec.clone(),
+                action: "skipped",
+                path: entry.target(&cli.clonepath),
+                revision: None,
+                remote: None,
+            }.print(cli.json);
+            continue;
+        }
+        match clone_one(cli, entry) {
+            Ok(()) => writeln!(state, "{}", repospec).wrap_err("Failed to record batch progress")?,
+            Err(e) if cli.fail_fast => {
+                warn!("Progress saved to {}; re-run with --resume to continue", state_path.display());
+                return Err(e.wrap_err(format!("Failed to clone {}", repospec)));
+            }
+            Err(e) if e.downcast_ref::<TimedOut>().is_some() => {
+                warn!("Skipping {}: {}", repospec, e);
+                failures += 1;
+                timed_out += 1;
+            }
+            Err(e) => {
+                error!("Failed to clone {}: {:?}", repospec, e);
+                failures += 1;
+            }
+        }
+    }
+
+    if failures > 0 {
+        warn!("Progress saved to {}; re-run with --resume to retry the failures", state_path.display());
+        return Err(eyre!("Failed to clone {} of {} repos ({} timed out)", failures, entries.len(), timed_out));
+    }
+
+    fs::remove_file(&state_path).wrap_err("Failed to remove batch state file")?;
+    Ok(())
+}
+
+fn batch_state_path(cli: &Cli, entries: &[CloneEntry]) -> Result<PathBuf> {
+    let state_dir = match (&cli.state_dir, env::var("XDG_CACHE_HOME")) {
+        (Some(dir), _) => dir.clone(),
+        (None, Ok(dir)) => PathBuf::from(dir).join("clone"),
+        (None, Err(_)) => PathBuf::from(env::var("HOME").wrap_err("Failed to get HOME environment variable")?).join(".cache/clone"),
     };
 
-    let full_clone_path = if cli.versioning {
-        PathBuf::from(&cli.clonepath).join(format!("{}/{}", cli.repospec, revision))
+    // key the state on the target layout and the repo set so unrelated batches don't collide
+    let mut specs: Vec<String> = entries.iter().map(|entry| format!("{}@{} {}", entry.repospec, entry.revision, entry.target(&cli.clonepath).display())).collect();
+    specs.sort();
+    let key = format!("{}\n{}", cli.clonepath, specs.join("\n"));
+
+    Ok(state_dir.join(format!("{:016x}.state", fnv1a(key.as_bytes()))))
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3))
+}
+
+fn read_manifest(path: &Path) -> Result<Vec<CloneEntry>> {
+    let content = fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read manifest {}", path.display()))?;
+    parse_manifest(&content).wrap_err_with(|| format!("Invalid manifest {}", path.display()))
+}
+
+// 'path slug [url [branch]]' per line, paths relative to --clonepath; '-' as the url means --remote
+fn parse_manifest(content: &str) -> Result<Vec<CloneEntry>> {
+    content.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(lineno, line)| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (path, repospec, url, branch) = match fields.as_slice() {
+                [path, repospec] => (path, repospec, None, None),
+                [path, repospec, url] => (path, repospec, Some(url), None),
+                [path, repospec, url, branch] => (path, repospec, Some(url), Some(branch)),
+                _ => return Err(eyre!("line {}: expected 'path slug [url [branch]]', got '{}'", lineno, line)),
+            };
+            Ok(CloneEntry {
+                repospec: repospec.to_string(),
+                revision: branch.unwrap_or(&"HEAD").to_string(),
+                path: Some(PathBuf::from(path)),
+                url: url.filter(|url| **url != "-").map(|url| url.to_string()),
+            })
+        })
+        .collect()
+}
+
+// --org names the org to list; a repospec alongside it only filters that org's repos
+fn expansion_pattern(cli: &Cli) -> Option<(String, String)> {
+    if let Some(org) = &cli.org {
+        let pattern = match &cli.repospec {
+            Some(repospec) if repospec.contains('/') => repospec.clone(),
+            Some(repospec) => format!("{}/{}", org, repospec),
+            None => format!("{}/*", org),
+        };
+        return Some((org.clone(), pattern));
+    }
+    cli.repospec.as_ref()
+        .filter(|repospec| repospec.contains(['*', '?', '[']))
+        .map(|pattern| (pattern.split('/').next().unwrap_or_default().to_string(), pattern.clone()))
+}
+
+fn list_org_repos(org: &str) -> Result<Vec<String>> {
+    debug!("Listing repos for {} via ls-github-repos", org);
+
+    let output = Command::new("ls-github-repos")
+        .arg(org)
+        .output()
+        .map_err(|e| missing_program("ls-github-repos", e))?;
+
+    if !output.status.success() {
+        return Err(eyre!("ls-github-repos failed for {}: {}", org, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+fn update_existing_repo(cli: &Cli, full_clone_path: &Path, revision: &str) -> Result<()> {
+    if cli.prune {
+        let pruned = prune_remote_refs(full_clone_path, cli.timeout.map(Duration::from_secs))?;
+        if cli.verbose {
+            eprintln!("Pruned {} stale remote-tracking ref(s) in {}", pruned, full_clone_path.display());
+        }
+    }
+
+    if cli.hard_reset {
+        return hard_reset_repo(full_clone_path, revision, cli.timeout.map(Duration::from_secs));
+    }
+
+    // a failed checkout or pull must fail the update, not be reported as "updated"
+    git_output(full_clone_path, &["checkout", revision])
+        .wrap_err_with(|| format!("Failed to checkout {}", revision))?;
+
+    let pull = run_bounded(
+        Command::new("git").args(["pull"]).current_dir(full_clone_path).stdout(Stdio::null()).stderr(Stdio::piped()),
+        cli.timeout.map(Duration::from_secs),
+    ).wrap_err("Failed to pull the latest changes")?;
+    if !pull.status.success() {
+        return Err(eyre!("git pull failed in {}: {}", full_clone_path.display(), String::from_utf8_lossy(&pull.stderr).trim()));
+    }
+
+    Ok(())
+}
+
+fn hard_reset_repo(full_clone_path: &Path, revision: &str, timeout: Option<Duration>) -> Result<()> {
+    let dirty = !git_output(full_clone_path, &["status", "--porcelain", "--ignored"])?.is_empty();
+
+    let fetch = run_bounded(
+        Command::new("git").args(["fetch", "origin"]).current_dir(full_clone_path).stdout(Stdio::null()).stderr(Stdio::piped()),
+        timeout,
+    ).wrap_err("Failed to fetch before resetting")?;
+    // resetting to a stale origin/* would throw away local work without matching the remote
+    if !fetch.status.success() {
+        return Err(eyre!("git fetch origin failed, leaving {} untouched: {}", full_clone_path.display(), String::from_utf8_lossy(&fetch.stderr).trim()));
+    }
+
+    // prefer the freshly fetched remote branch so a named branch lands on upstream, not the stale local one
+    let upstream = if revision == "HEAD" { "origin/HEAD".to_string() } else { format!("origin/{}", revision) };
+    let target = if git_output(full_clone_path, &["rev-parse", "--verify", "--quiet", &upstream]).is_ok() {
+        upstream
     } else {
-        PathBuf::from(&cli.clonepath).join(&cli.repospec)
+        revision.to_string()
     };
 
+    if revision != "HEAD" {
+        git_output(full_clone_path, &["checkout", "--force", revision])?;
+    }
+    git_output(full_clone_path, &["reset", "--hard", &target])?;
+    git_output(full_clone_path, &["clean", "-xfd"])?;
+
+    if dirty {
+        eprintln!("Discarded local changes in {}", full_clone_path.display());
+    }
+    Ok(())
+}
+
+fn git_output(path: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(path)
+        .output()
+        .wrap_err_with(|| format!("Failed to run git {}", args.join(" ")))?;
+
+    if !output.status.success() {
+        return Err(eyre!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn prune_remote_refs(full_clone_path: &Path, timeout: Option<Duration>) -> Result<usize> {
+    let output = run_bounded(
+        Command::new("git")
+            .args(["fetch", "--prune"])
+            .current_dir(full_clone_path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+        timeout,
+    ).wrap_err("Failed to prune remote-tracking refs")?;
+
+    if !output.status.success() {
+        return Err(eyre!("git fetch --prune failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    // fetch reports each pruned ref as " - [deleted] (none) -> origin/branch" on stderr
+    let pruned = String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .filter(|line| line.contains("[deleted]"))
+        .count();
+    Ok(pruned)
+}
+
+fn clone_new_repo(cli: &Cli, repospec: &str, revision: &str, full_clone_path: &Path, url: Option<&str>) -> Result<String> {
     debug!("Attempting to clone into {:?}", full_clone_path);
+    prepare_parents(full_clone_path, cli.no_parents)?;
 
-    let mirror_option = cli.mirrorpath.as_ref().map(|mirror|
-        format!("--reference {}/{}.git", mirror, cli.repospec)
-    );
+    let mut clone_options: Vec<String> = cli.mirrorpath.as_ref().map(|mirror|
+        format!("--reference {}/{}.git", mirror, repospec)
+    ).into_iter().collect();
+    if let Some(filter) = &cli.partial {
+        clone_options.push(format!("--filter={}", filter));
+    }
 
-    let ssh_key = find_ssh_key_for_org(&cli.repospec)?;
-    if let Some(key) = ssh_key {
-        if !attempt_clone_with_ssh(&cli.repospec, &full_clone_path, &cli.remote, &mirror_option, &key, cli.verbose)? {
-            warn!("SSH failed, trying HTTPS...");
-            if !attempt_clone_with_ssh(&cli.repospec, &full_clone_path, REMOTE_URLS[1], &mirror_option, &key, cli.verbose)? {
-                error!("Failed to clone repository using all configured remotes.");
-                return Err(eyre!("Failed to clone repository using all configured remotes."));
-            }
+    let prefer = match cli.prefer {
+        Some(prefer) => prefer,
+        None => match find_org_setting(cli, repospec, "prefer")? {
+            Some(prefer) => Prefer::from_str(&prefer, true).map_err(|e| eyre!("Invalid prefer in clone.cfg: {}", e))?,
+            None => Prefer::Ssh,
+        },
+    };
+    // a manifest's own URL is the only place to clone that repo from
+    let remotes: Vec<(&str, String)> = match url {
+        Some(url) => vec![(url, url.to_string())],
+        None => match prefer {
+            Prefer::Ssh => [cli.remote.as_str(), REMOTE_URLS[1]],
+            Prefer::Https => [REMOTE_URLS[1], cli.remote.as_str()],
+        }.into_iter().map(|remote| (remote, format!("{}/{}", remote, repospec))).collect(),
+    };
+
+    let ssh_key = find_ssh_key_for_org(cli, repospec)?;
+    let mut cloned_from = None;
+    for (i, (remote, repo_url)) in remotes.iter().enumerate() {
+        if i > 0 {
+            warn!("{} failed, trying {}...", remotes[i - 1].0, remote);
         }
-    } else {
-        if !attempt_clone(&cli.repospec, &full_clone_path, &cli.remote, &mirror_option, cli.verbose)? {
-            warn!("SSH failed, trying HTTPS...");
-            if !attempt_clone(&cli.repospec, &full_clone_path, REMOTE_URLS[1], &mirror_option, cli.verbose)? {
-                error!("Failed to clone repository using all configured remotes.");
-                return Err(eyre!("Failed to clone repository using all configured remotes."));
-            }
+        let cloned = match &ssh_key {
+            Some(key) => attempt_clone_with_ssh(repospec, full_clone_path, repo_url, &clone_options, key, cli.timeout.map(Duration::from_secs))?,
+            None => attempt_clone(repospec, full_clone_path, repo_url, &clone_options, cli.timeout.map(Duration::from_secs))?,
+        };
+        if cloned {
+            cloned_from = Some(remote.to_string());
+            break;
+        }
+    }
+    let Some(remote) = cloned_from else {
+        error!("Failed to clone repository using all configured remotes.");
+        return Err(eyre!("Failed to clone repository using all configured remotes."));
+    };
+
+    git_output(full_clone_path, &["checkout", revision])
+        .wrap_err_with(|| format!("Failed to checkout {}", revision))?;
+
+    Ok(remote)
+}
+
+// only commit-named directories directly under the repospec are candidates, newest kept
+fn prune_versions(repo_dir: &Path, keep: usize, current: &Path) -> Result<Vec<PathBuf>> {
+    let is_sha = |name: &str| matches!(name.len(), 40 | 64) && name.bytes().all(|b| b.is_ascii_hexdigit());
+
+    // newest commit first; the checkout just reported always counts as one of the kept versions
+    let mut versions: Vec<(Option<i64>, PathBuf)> = fs::read_dir(repo_dir)
+        .wrap_err_with(|| format!("Failed to read {}", repo_dir.display()))?
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .filter(|entry| entry.file_name().to_str().is_some_and(is_sha))
+        .map(|entry| entry.path())
+        .filter(|path| path != current)
+        .map(|path| (head_commit_time(&path), path))
+        .collect();
+    versions.sort_by(|(t1, _), (t2, _)| t2.cmp(t1));
+
+    let pruned: Vec<PathBuf> = versions.into_iter().skip(keep.saturating_sub(1)).map(|(_, path)| path).collect();
+    for path in &pruned {
+        fs::remove_dir_all(path).wrap_err_with(|| format!("Failed to prune {}", path.display()))?;
+    }
+    Ok(pruned)
+}
+
+fn head_commit_time(path: &Path) -> Option<i64> {
+    git_output(path, &["log", "-1", "--format=%ct"]).ok()?.parse().ok()
+}
+
+fn prepare_parents(full_clone_path: &Path, no_parents: bool) -> Result<()> {
+    let Some(parent) = full_clone_path.parent().filter(|parent| !parent.as_os_str().is_empty()) else {
+        return Ok(());
+    };
+    if no_parents {
+        if !parent.is_dir() {
+            return Err(eyre!("Parent directory {} does not exist (--no-parents)", parent.display()));
         }
+        return Ok(());
     }
+    fs::create_dir_all(parent).wrap_err_with(|| format!("Failed to create {}", parent.display()))
+}
 
-    Command::new("git")
-        .args(["checkout", &revision])
+fn run_post_clone(cli: &Cli, repospec: &str, checkout_path: &Path) -> Result<()> {
+    let hook = match &cli.post_clone {
+        Some(hook) => Some(hook.clone()),
+        None => find_org_setting(cli, repospec, "postclone")?,
+    };
+    let Some(hook) = hook else {
+        return Ok(());
+    };
+
+    debug!("Running post-clone command in {:?}: {}", checkout_path, hook);
+
+    let status = Command::new("sh")
+        .args(["-c", &hook])
+        .current_dir(checkout_path)
         .stdout(Stdio::null())
         .status()
-        .wrap_err("Failed to checkout the specified revision")?;
+        .wrap_err("Failed to execute post-clone command")?;
+
+    if !status.success() {
+        if cli.ignore_hook_errors {
+            warn!("Post-clone command failed for {}: {}", repospec, status);
+        } else {
+            return Err(eyre!("Post-clone command failed for {}: {}", repospec, status));
+        }
+    }
 
     Ok(())
 }
 
-fn fetch_revision_sha(remote_url: &str, repospec: &str, _verbose: bool) -> Result<String> {
+fn remote_repo_url(remote_url: &str, repospec: &str) -> String {
     let separator = if remote_url.starts_with("git@") { ":" } else { "/" };
-    let repo_url = format!("{}{}{}", remote_url, separator, repospec);
+    format!("{}{}{}", remote_url, separator, repospec)
+}
 
-    let command_args = ["ls-remote", &repo_url, "HEAD"];
+fn fetch_revision_sha(repo_url: &str, timeout: Option<Duration>) -> Result<String> {
+    let command_args = ["ls-remote", repo_url, "HEAD"];
     debug!("Executing git command with args: {:?}", command_args);
 
-    let output = Command::new("git")
-        .args(&command_args)
-        .stdout(Stdio::null())
-        .output()
-        .wrap_err("Failed to execute ls-remote")?;
+    let output = run_bounded(
+        Command::new("git").args(command_args).stdout(Stdio::piped()),
+        timeout,
+    ).wrap_err("Failed to execute ls-remote")?;
 
     debug!("ls-remote output: {:?}", String::from_utf8_lossy(&output.stdout));
 
-    let output_str = String::from_utf8(output.stdout).wrap_err("Failed to parse ls-remote output")?;
+    let output_str = String::from_utf8_lossy(&output.stdout);
     let sha = output_str.lines()
         .filter(|line| line.contains("HEAD"))
         .filter_map(|line| line.split_whitespace().next())
@@ -156,57 +735,158 @@ fn fetch_revision_sha(remote_url: &str, repospec: &str, _verbose: bool) -> Resul
     Ok(sha)
 }
 
-fn attempt_clone_with_ssh(repospec: &str, full_clone_path: &Path, remote_url: &str, mirror_option: &Option<String>, ssh_key: &str, _verbose: bool) -> Result<bool> {
+fn attempt_clone_with_ssh(repospec: &str, full_clone_path: &Path, repo_url: &str, clone_options: &[String], ssh_key: &str, timeout: Option<Duration>) -> Result<bool> {
     let mut clone_command = Command::new("git");
     clone_command.arg("clone")
-        .arg(format!("{}/{}", remote_url, repospec))
+        .arg(repo_url)
         .arg(full_clone_path)
         .env("GIT_SSH_COMMAND", format!("/usr/bin/ssh -i {}", ssh_key))
         .stdout(Stdio::null());
 
-    if let Some(ref mirror) = mirror_option {
-        clone_command.arg(mirror);
-    }
+    clone_command.args(clone_options);
 
     debug!("Executing: {:?}", clone_command);
 
-    let clone_status = clone_command.status().wrap_err("Failed to execute git clone with SSH")?;
+    let clone_status = match run_bounded(&mut clone_command, timeout) {
+        Ok(output) => output.status,
+        Err(e) if e.downcast_ref::<TimedOut>().is_some() => return timed_out_attempt(repospec, full_clone_path, &e),
+        Err(e) => return Err(e.wrap_err("Failed to execute git clone with SSH")),
+    };
     if !clone_status.success() {
         error!("Cloning failed for {}: {}", repospec, clone_status);
     }
     Ok(clone_status.success())
 }
 
-fn attempt_clone(repospec: &str, full_clone_path: &Path, remote_url: &str, mirror_option: &Option<String>, _verbose: bool) -> Result<bool> {
+fn attempt_clone(repospec: &str, full_clone_path: &Path, repo_url: &str, clone_options: &[String], timeout: Option<Duration>) -> Result<bool> {
     let mut clone_command = Command::new("git");
     clone_command.arg("clone")
-        .arg(format!("{}/{}", remote_url, repospec))
+        .arg(repo_url)
         .arg(full_clone_path)
         .stdout(Stdio::null());
 
-    if let Some(ref mirror) = mirror_option {
-        clone_command.arg(mirror);
-    }
+    clone_command.args(clone_options);
 
     debug!("Executing: {:?}", clone_command);
 
-    let clone_status = clone_command.status().wrap_err("Failed to execute git clone")?;
+    let clone_status = match run_bounded(&mut clone_command, timeout) {
+        Ok(output) => output.status,
+        Err(e) if e.downcast_ref::<TimedOut>().is_some() => return timed_out_attempt(repospec, full_clone_path, &e),
+        Err(e) => return Err(e.wrap_err("Failed to execute git clone")),
+    };
     if !clone_status.success() {
         error!("Cloning failed for {}: {}", repospec, clone_status);
     }
     Ok(clone_status.success())
 }
 
-fn find_ssh_key_for_org(repospec: &str) -> Result<Option<String>> {
+// a killed clone leaves a partial checkout that later runs would mistake for a repo to update
+fn timed_out_attempt(repospec: &str, full_clone_path: &Path, e: &eyre::Report) -> Result<bool> {
+    warn!("Cloning {} {}", repospec, e);
+    if full_clone_path.exists() {
+        fs::remove_dir_all(full_clone_path)
+            .wrap_err_with(|| format!("Failed to remove partial clone {}", full_clone_path.display()))?;
+    }
+    Ok(false)
+}
+
+// kill network operations after the timeout so one unreachable remote can't stall a batch
+fn run_bounded(command: &mut Command, timeout: Option<Duration>) -> Result<Output> {
+    let mut child = command.spawn()
+        .map_err(|e| missing_program(&command.get_program().to_string_lossy(), e))?;
+    // drain piped output on threads so a chatty child can't block on a full pipe while we poll
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let status = match timeout {
+        None => child.wait()?,
+        Some(timeout) => {
+            let deadline = Instant::now() + timeout;
+            loop {
+                if let Some(status) = child.try_wait()? {
+                    break status;
+                }
+                if Instant::now() >= deadline {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(TimedOut(timeout).into());
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        }
+    };
+
+    let collect = |handle: Option<JoinHandle<Vec<u8>>>| handle.and_then(|handle| handle.join().ok()).unwrap_or_default();
+    Ok(Output { status, stdout: collect(stdout), stderr: collect(stderr) })
+}
+
+fn ensure_installed(program: &str) -> Result<()> {
+    Command::new(program)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|_| ())
+        .map_err(|e| missing_program(program, e))
+}
+
+// spawn reports a missing binary as a bare "No such file or directory"; name the program instead
+fn missing_program(program: &str, e: io::Error) -> eyre::Report {
+    if e.kind() == io::ErrorKind::NotFound {
+        eyre!("{} not found on PATH; please install {}", program, program)
+    } else {
+        eyre!("Failed to run {}: {}", program, e)
+    }
+}
+
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> Option<JoinHandle<Vec<u8>>> {
+    pipe.map(|mut pipe| thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    }))
+}
+
+fn find_ssh_key_for_org(cli: &Cli, repospec: &str) -> Result<Option<String>> {
+    find_org_setting(cli, repospec, "sshkey")
+}
+
+fn config_path(cli: &Cli) -> Result<PathBuf> {
+    if let Some(config) = &cli.config {
+        return Ok(config.clone());
+    }
+    if let Ok(config) = env::var("CLONE_CFG") {
+        return Ok(PathBuf::from(config));
+    }
     let home_dir = env::var("HOME").wrap_err("Failed to get HOME environment variable")?;
-    let config_path = env::var("CLONE_CFG")
-        .unwrap_or_else(|_| format!("{}/.config/clone/clone.cfg", home_dir));
+    Ok(PathBuf::from(home_dir).join(".config/clone/clone.cfg"))
+}
+
+fn init_config(cli: &Cli) -> Result<()> {
+    let config_path = config_path(cli)?;
+    if config_path.exists() {
+        println!("{} already exists; leaving it alone", config_path.display());
+        return Ok(());
+    }
+
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent).wrap_err_with(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(&config_path, EXAMPLE_CONFIG).wrap_err_with(|| format!("Failed to write {}", config_path.display()))?;
+    println!("Created {}", config_path.display());
+
+    Ok(())
+}
+
+fn find_org_setting(cli: &Cli, repospec: &str, key: &str) -> Result<Option<String>> {
+    let config_path = config_path(cli)?;
 
-    if !Path::new(&config_path).exists() {
+    if !config_path.exists() {
         warn!("Configuration file not found: {:?}", config_path);
         return Ok(None);
     }
 
+    let config_path = config_path.to_string_lossy().into_owned();
     let cfg = ini!(&config_path);
     if cfg.is_empty() {
         return Err(eyre!("Failed to load configuration file"));
@@ -214,10 +894,314 @@ fn find_ssh_key_for_org(repospec: &str) -> Result<Option<String>> {
 
     let org_name = repospec.split('/').next().ok_or_else(|| eyre!("Invalid repospec format"))?;
     let section_key = format!("org.{}", org_name);
-    let ssh_key_map = cfg.get(&section_key).or_else(|| cfg.get("org.default"))
+    let settings = cfg.get(&section_key).or_else(|| cfg.get("org.default"))
         .ok_or_else(|| eyre!("Configuration section not found"))?;
 
-    let ssh_key = ssh_key_map.get("sshkey").cloned().flatten();
+    Ok(settings.get(key).cloned().flatten())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_partial_clone_blob_none() {
+        let root = env::temp_dir().join(format!("clone-partial-{}", std::process::id()));
+        let source = root.join("remote/org/repo");
+        fs::create_dir_all(&source).unwrap();
+        git(&source, &["init", "-q"]);
+        git(&source, &["config", "uploadpack.allowFilter", "true"]);
+        fs::write(source.join("README"), "hello").unwrap();
+        git(&source, &["add", "README"]);
+        git(&source, &["commit", "-q", "-m", "init"]);
+
+        let remote_url = format!("file://{}", root.join("remote").display());
+        let target = root.join("out/org/repo");
+        let options = vec!["--filter=blob:none".to_string()];
+        assert!(attempt_clone("org/repo", &target, &format!("{}/org/repo", remote_url), &options, None).unwrap());
+
+        let filter = Command::new("git")
+            .args(["config", "remote.origin.partialclonefilter"])
+            .current_dir(&target)
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&filter.stdout).trim(), "blob:none");
+        assert_eq!(fs::read_to_string(target.join("README")).unwrap(), "hello");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_run_bounded_times_out() {
+        let err = run_bounded(Command::new("sleep").arg("5"), Some(Duration::from_millis(200))).unwrap_err();
+        assert!(err.downcast_ref::<TimedOut>().is_some());
+
+        let output = run_bounded(Command::new("echo").arg("done").stdout(Stdio::piped()), Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "done");
+    }
+
+    #[test]
+    fn test_timed_out_attempt_cleans_up_and_falls_through() {
+        let root = env::temp_dir().join(format!("clone-attempt-timeout-{}", std::process::id()));
+        let source = root.join("remote/org/repo");
+        fs::create_dir_all(&source).unwrap();
+        git(&source, &["init", "-q"]);
+        git(&source, &["commit", "-q", "--allow-empty", "-m", "init"]);
+
+        // an upload-pack that stalls stands in for a hanging SSH transport
+        let repo_url = format!("file://{}", source.display());
+        let target = root.join("out/org/repo");
+        let hang = vec!["--upload-pack=sleep 5; git-upload-pack".to_string()];
+        let timed_out = attempt_clone("org/repo", &target, &repo_url, &hang, Some(Duration::from_millis(500)));
+        let left_behind = target.exists();
+        let next = attempt_clone("org/repo", &target, &repo_url, &[], Some(Duration::from_secs(30)));
+        let cloned = target.join(".git").is_dir();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(!timed_out.unwrap());
+        assert!(!left_behind);
+        assert!(next.unwrap());
+        assert!(cloned);
+    }
+
+    #[test]
+    fn test_missing_program_error() {
+        let program = "git-tools-no-such-program";
+        let err = ensure_installed(program).unwrap_err();
+        assert_eq!(err.to_string(), format!("{} not found on PATH; please install {}", program, program));
+
+        let err = run_bounded(&mut Command::new(program), None).unwrap_err();
+        assert!(err.to_string().contains("not found on PATH"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_clone_into_missing_parents() {
+        let root = env::temp_dir().join(format!("clone-parents-{}", std::process::id()));
+        let source = root.join("remote/org/repo");
+        fs::create_dir_all(&source).unwrap();
+        git(&source, &["init", "-q"]);
+        git(&source, &["commit", "-q", "--allow-empty", "-m", "init"]);
+
+        let remote_url = format!("file://{}", root.join("remote").display());
+        let target = root.join("out/github.com/deep/layout/org/repo");
+        assert!(prepare_parents(&target, true).is_err());
+        prepare_parents(&target, false).unwrap();
+        assert!(target.parent().unwrap().is_dir());
+        assert!(attempt_clone("org/repo", &target, &format!("{}/org/repo", remote_url), &[], None).unwrap());
+        assert!(target.join(".git").is_dir());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_versioned_clone_keeps_newest_commit() {
+        let root = env::temp_dir().join(format!("clone-versioned-{}", std::process::id()));
+        let source = root.join("remote/org/repo");
+        fs::create_dir_all(&source).unwrap();
+        git(&source, &["init", "-q"]);
+        git(&source, &["commit", "-q", "--allow-empty", "-m", "first"]);
 
-    Ok(ssh_key)
+        let remote_url = format!("file://{}", root.join("remote").display());
+        let clonepath = root.join("out");
+        let config = root.join("clone.cfg");
+        let cli = Cli::parse_from(["clone", "org/repo", "--remote", &remote_url, "--clonepath", clonepath.to_str().unwrap(), "--config", config.to_str().unwrap(), "--versioning", "--keep", "1"]);
+        clone_one(&cli, &CloneEntry::new("org/repo", "HEAD")).unwrap();
+        let first = head_sha(&source).unwrap();
+        assert!(clonepath.join("org/repo").join(&first).join(".git").is_dir());
+
+        git(&source, &["commit", "-q", "--allow-empty", "-m", "second"]);
+        clone_one(&cli, &CloneEntry::new("org/repo", "HEAD")).unwrap();
+        let second = head_sha(&source).unwrap();
+        let versions: Vec<String> = fs::read_dir(clonepath.join("org/repo")).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        let checked_out = head_sha(&clonepath.join("org/repo").join(&second));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(versions, vec![second.clone()]);
+        assert_eq!(checked_out, Some(second));
+    }
+
+    #[test]
+    fn test_expansion_pattern_uses_org() {
+        let pattern = |args: &[&str]| expansion_pattern(&Cli::parse_from([&["clone"], args].concat()));
+        assert_eq!(pattern(&["--org", "myorg"]), Some(("myorg".to_string(), "myorg/*".to_string())));
+        assert_eq!(pattern(&["--org", "myorg", "api-*"]), Some(("myorg".to_string(), "myorg/api-*".to_string())));
+        assert_eq!(pattern(&["--org", "a", "b/*"]), Some(("a".to_string(), "b/*".to_string())));
+        assert_eq!(pattern(&["other/*"]), Some(("other".to_string(), "other/*".to_string())));
+        assert_eq!(pattern(&["org/repo"]), None);
+    }
+
+    #[test]
+    fn test_matching_repospecs_ignores_case() {
+        let repospecs = vec!["Acme/API-Gateway".to_string(), "acme/api-users".to_string(), "acme/web".to_string()];
+        assert_eq!(matching_repospecs(repospecs, "acme/api-*").unwrap(), vec!["Acme/API-Gateway", "acme/api-users"]);
+        assert!(matching_repospecs(vec![], "acme/[").is_err());
+    }
+
+    #[test]
+    fn test_hard_reset_keeps_tree_when_fetch_fails() {
+        let root = env::temp_dir().join(format!("clone-reset-{}", std::process::id()));
+        let checkout = root.join("checkout");
+        fs::create_dir_all(&checkout).unwrap();
+        git(&checkout, &["init", "-q"]);
+        git(&checkout, &["commit", "-q", "--allow-empty", "-m", "init"]);
+        git(&checkout, &["remote", "add", "origin", root.join("missing").to_str().unwrap()]);
+        fs::write(checkout.join("work.txt"), "local changes").unwrap();
+
+        let result = hard_reset_repo(&checkout, "HEAD", None);
+        let kept = checkout.join("work.txt").exists();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(result.is_err());
+        assert!(kept);
+    }
+
+    #[test]
+    fn test_update_fails_on_bad_revision() {
+        let root = env::temp_dir().join(format!("clone-update-{}", std::process::id()));
+        let source = root.join("remote/org/repo");
+        fs::create_dir_all(&source).unwrap();
+        git(&source, &["init", "-q"]);
+        git(&source, &["commit", "-q", "--allow-empty", "-m", "init"]);
+        let checkout = root.join("out/org/repo");
+        assert!(attempt_clone("org/repo", &checkout, &format!("file://{}", source.display()), &[], None).unwrap());
+
+        let cli = Cli::parse_from(["clone", "org/repo"]);
+        let updated = update_existing_repo(&cli, &checkout, "HEAD");
+        let missing = update_existing_repo(&cli, &checkout, "no-such-branch");
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(updated.is_ok(), "{:?}", updated);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_clone_fails_on_bad_revision() {
+        let root = env::temp_dir().join(format!("clone-new-revision-{}", std::process::id()));
+        let source = root.join("remote/org/repo");
+        fs::create_dir_all(&source).unwrap();
+        git(&source, &["init", "-q"]);
+        git(&source, &["commit", "-q", "--allow-empty", "-m", "init"]);
+
+        let remote_url = format!("file://{}", root.join("remote").display());
+        let config = root.join("clone.cfg");
+        let cli = Cli::parse_from(["clone", "org/repo", "--remote", &remote_url, "--config", config.to_str().unwrap()]);
+        let cloned = clone_new_repo(&cli, "org/repo", "HEAD", &root.join("out/good"), None);
+        let missing = clone_new_repo(&cli, "org/repo", "no-such-branch", &root.join("out/bad"), None);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(cloned.is_ok(), "{:?}", cloned);
+        assert!(missing.unwrap_err().to_string().contains("no-such-branch"));
+    }
+
+    #[test]
+    fn test_fail_fast_stops_at_first_failure() {
+        let root = env::temp_dir().join(format!("clone-fail-fast-{}", std::process::id()));
+        let source = root.join("remote/org/repo");
+        fs::create_dir_all(&source).unwrap();
+        git(&source, &["init", "-q"]);
+        git(&source, &["commit", "-q", "--allow-empty", "-m", "init"]);
+        fs::create_dir_all(root.join("out/org")).unwrap();
+
+        // --no-parents fails the first entry before any network access; the second would clone fine
+        let remote_url = format!("file://{}", root.join("remote").display());
+        let (clonepath, config, state_dir) = (root.join("out"), root.join("clone.cfg"), root.join("state"));
+        let cli = Cli::parse_from([
+            "clone", "--remote", &remote_url, "--clonepath", clonepath.to_str().unwrap(), "--config", config.to_str().unwrap(),
+            "--state-dir", state_dir.to_str().unwrap(), "--no-parents", "--fail-fast", "--org", "org",
+        ]);
+        let entries = vec![CloneEntry::new("missing/repo", "HEAD"), CloneEntry::new("org/repo", "HEAD")];
+        let result = clone_all(&cli, &entries);
+        let attempted_second = root.join("out/org/repo").exists();
+        let state_kept = batch_state_path(&cli, &entries).unwrap().exists();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(result.is_err());
+        assert!(!attempted_second);
+        assert!(state_kept);
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let entries = parse_manifest("\
+# path slug [url [branch]]
+work/api acme/api git@github.com:acme/api.git develop
+work/web acme/web -
+oss/tool someone/tool
+").unwrap();
+        assert_eq!(entries, vec![
+            CloneEntry { repospec: "acme/api".to_string(), revision: "develop".to_string(), path: Some(PathBuf::from("work/api")), url: Some("git@github.com:acme/api.git".to_string()) },
+            CloneEntry { repospec: "acme/web".to_string(), revision: "HEAD".to_string(), path: Some(PathBuf::from("work/web")), url: None },
+            CloneEntry { repospec: "someone/tool".to_string(), revision: "HEAD".to_string(), path: Some(PathBuf::from("oss/tool")), url: None },
+        ]);
+        assert!(parse_manifest("acme/api\n").is_err());
+    }
+
+    #[test]
+    fn test_prune_versions_keeps_newest_commit() {
+        let repo_dir = env::temp_dir().join(format!("clone-keep-{}", std::process::id()));
+        let shas = ["a".repeat(40), "b".repeat(40), "c".repeat(40)];
+        // created oldest-commit-last so directory mtimes disagree with commit times
+        for (sha, date) in shas.iter().zip(["2022-01-01T00:00:00", "2021-01-01T00:00:00", "2020-01-01T00:00:00"]) {
+            let version = repo_dir.join(sha);
+            fs::create_dir_all(&version).unwrap();
+            git(&version, &["init", "-q"]);
+            let status = Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "-q", "--allow-empty", "-m", sha])
+                .env("GIT_COMMITTER_DATE", date)
+                .current_dir(&version)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+        fs::create_dir_all(repo_dir.join("notes")).unwrap();
+
+        let pruned = prune_versions(&repo_dir, 2, &repo_dir.join(&shas[2])).unwrap();
+        let remaining = |name: &str| repo_dir.join(name).exists();
+        let kept = (remaining(&shas[0]), remaining(&shas[2]), remaining("notes"));
+        fs::remove_dir_all(&repo_dir).unwrap();
+
+        assert_eq!(pruned, vec![repo_dir.join(&shas[1])]);
+        assert_eq!(kept, (true, true, true));
+    }
+
+    #[test]
+    fn test_unchanged_checkout_survives_keep() {
+        let root = env::temp_dir().join(format!("clone-unchanged-keep-{}", std::process::id()));
+        let source = root.join("remote/org/repo");
+        fs::create_dir_all(&source).unwrap();
+        git(&source, &["init", "-q"]);
+        git(&source, &["commit", "-q", "--allow-empty", "-m", "first"]);
+
+        let remote_url = format!("file://{}", root.join("remote").display());
+        let (clonepath, config) = (root.join("out"), root.join("clone.cfg"));
+        let args = ["clone", "org/repo", "--remote", &remote_url, "--clonepath", clonepath.to_str().unwrap(), "--config", config.to_str().unwrap(), "--versioning"];
+        let cli = Cli::parse_from(args);
+        clone_one(&cli, &CloneEntry::new("org/repo", "HEAD")).unwrap();
+        let first = head_sha(&source).unwrap();
+        git(&source, &["commit", "-q", "--allow-empty", "-m", "second"]);
+        clone_one(&cli, &CloneEntry::new("org/repo", "HEAD")).unwrap();
+        let second = head_sha(&source).unwrap();
+
+        // the remote rewinds, so the next run reports the older checkout as unchanged
+        git(&source, &["reset", "-q", "--hard", &first]);
+        let keep_one = Cli::parse_from([&args[..], &["--keep", "1"]].concat());
+        clone_one(&keep_one, &CloneEntry::new("org/repo", "HEAD")).unwrap();
+        let kept = (clonepath.join("org/repo").join(&first).is_dir(), clonepath.join("org/repo").join(&second).exists());
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(kept, (true, false));
+    }
 }
@@ -1,8 +1,9 @@
 use clap::Parser;
 use eyre::{Result, eyre, WrapErr};
-use git2::Repository;
+use git2::{Commit, Repository};
 use chrono::{Local, Duration, Utc, TimeZone};
 use log::{info, debug};
+use regex::Regex;
 
 mod built_info {
     include!(concat!(env!("OUT_DIR"), "/git_describe.rs"));
@@ -20,6 +21,12 @@ struct Args {
     show_author: bool,
     #[clap(short = 's', long, value_parser = parse_span, default_value = "6m")]
     span: (Option<Duration>, Duration),
+    #[clap(short = 'g', long, help = "only print refs whose commit message matches this regex")]
+    grep: Option<Regex>,
+    #[clap(long, help = "date the ref by its oldest commit not on --base instead of its tip; --grep and --show-author still use the tip")]
+    full_history: bool,
+    #[clap(long, requires = "full_history", help = "branch --full-history excludes [default: origin/HEAD, main or master]")]
+    base: Option<String>,
     #[clap(value_parser)]
     ref_: String,
 }
@@ -32,16 +39,28 @@ fn main() -> Result<()> {
     let repo = Repository::discover(".")?;
     debug!("Repository discovered");
 
-    test_ref(&repo, &args.ref_, args.show_date, args.show_author, args.span)?;
+    let base = match (args.full_history, &args.base) {
+        (false, _) => None,
+        (true, Some(base)) => Some(base.clone()),
+        (true, None) => Some(default_base(&repo)?),
+    };
+
+    test_ref(&repo, &args.ref_, args.show_date, args.show_author, args.span, args.grep.as_ref(), base.as_deref())?;
     Ok(())
 }
 
-fn test_ref(repo: &Repository, ref_: &str, show_date: bool, show_author: bool, span: (Option<Duration>, Duration)) -> Result<()> {
+fn test_ref(repo: &Repository, ref_: &str, show_date: bool, show_author: bool, span: (Option<Duration>, Duration), grep: Option<&Regex>, base: Option<&str>) -> Result<()> {
     let obj = repo.revparse_single(ref_).wrap_err("Failed to parse ref")?;
-    let commit = obj.peel_to_commit().wrap_err("Failed to peel object to commit")?;
-    let author = commit.author();
+    let tip = obj.peel_to_commit().wrap_err("Failed to peel object to commit")?;
+    // with --full-history the oldest commit unique to the ref supplies the date; merged refs fall back to the tip
+    let dated = match base {
+        Some(base) => oldest_unique_commit(repo, &tip, base)?,
+        None => None,
+    };
+    let author = tip.author();
     let author_name = author.name().ok_or_else(|| eyre!("Author name not found"))?;
-    let commit_time = Utc.timestamp_opt(commit.time().seconds(), 0).single().ok_or_else(|| eyre!("Invalid timestamp"))?;
+    let commit_seconds = dated.as_ref().unwrap_or(&tip).time().seconds();
+    let commit_time = Utc.timestamp_opt(commit_seconds, 0).single().ok_or_else(|| eyre!("Invalid timestamp"))?;
     let now = Local::now();
 
     debug!("Commit Time: {}", commit_time);
@@ -53,6 +72,11 @@ fn test_ref(repo: &Repository, ref_: &str, show_date: bool, show_author: bool, s
 
     info!("Checking between {} and {}", since_date, until_date);
 
+    if !message_matches(tip.message().unwrap_or_default(), grep) {
+        debug!("No output: commit message does not match {:?}", grep);
+        return Ok(());
+    }
+
     if since_date < commit_time && commit_time < until_date {
         if show_date {
             println!("{} ", commit_time);
@@ -67,6 +91,37 @@ fn test_ref(repo: &Repository, ref_: &str, show_date: bool, show_author: bool, s
     Ok(())
 }
 
+fn oldest_unique_commit<'r>(repo: &'r Repository, tip: &Commit, base: &str) -> Result<Option<Commit<'r>>> {
+    let base = repo.revparse_single(base)
+        .and_then(|obj| obj.peel_to_commit())
+        .wrap_err_with(|| format!("Failed to resolve base {}", base))?;
+
+    let mut walk = repo.revwalk()?;
+    walk.push(tip.id())?;
+    walk.hide(base.id())?;
+
+    let mut oldest: Option<Commit> = None;
+    for oid in walk {
+        let commit = repo.find_commit(oid?)?;
+        if oldest.as_ref().is_none_or(|oldest| commit.time().seconds() < oldest.time().seconds()) {
+            oldest = Some(commit);
+        }
+    }
+    Ok(oldest)
+}
+
+fn default_base(repo: &Repository) -> Result<String> {
+    ["refs/remotes/origin/HEAD", "main", "master"]
+        .into_iter()
+        .find(|candidate| repo.revparse_single(candidate).is_ok())
+        .map(str::to_string)
+        .ok_or_else(|| eyre!("No default branch found for --full-history; pass --base"))
+}
+
+fn message_matches(message: &str, grep: Option<&Regex>) -> bool {
+    grep.is_none_or(|re| re.is_match(message))
+}
+
 fn parse_span(s: &str) -> Result<(Option<Duration>, Duration)> {
     let parts: Vec<&str> = s.split(':').collect();
     match parts.len() {
@@ -87,3 +142,53 @@ fn parse_duration(s: &str) -> Result<Duration> {
         _ => Err(eyre!("Invalid time unit")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_matches() {
+        let grep = Regex::new(r"^(WIP|PROJ-\d+)").unwrap();
+
+        assert!(message_matches("WIP: half done", Some(&grep)));
+        assert!(message_matches("PROJ-123 fix the thing\n\nbody", Some(&grep)));
+        assert!(!message_matches("Fix the thing\n\nWIP in body", Some(&grep)));
+        assert!(message_matches("anything", None));
+    }
+
+    fn commit(repo: &Repository, refname: &str, message: &str, seconds: i64, parents: &[&Commit]) -> git2::Oid {
+        let sig = git2::Signature::new("test", "test@example.com", &git2::Time::new(seconds, 0)).unwrap();
+        let tree = repo.find_tree(repo.index().unwrap().write_tree().unwrap()).unwrap();
+        repo.commit(Some(refname), &sig, &sig, message, &tree, parents).unwrap()
+    }
+
+    #[test]
+    fn test_oldest_unique_commit() {
+        let root = std::env::temp_dir().join(format!("filter-ref-history-{}", std::process::id()));
+        let repo = Repository::init(&root).unwrap();
+        let base = commit(&repo, "refs/heads/main", "base", 1_000, &[]);
+        let base = repo.find_commit(base).unwrap();
+
+        // a branch started long ago and touched again recently is dated by its first unique commit
+        let mut parent = base.clone();
+        for (message, seconds) in [("start", 2_000), ("middle", 3_000), ("recent", 9_000)] {
+            let oid = commit(&repo, "refs/heads/feature", message, seconds, &[&parent]);
+            parent = repo.find_commit(oid).unwrap();
+        }
+        repo.branch("merged", &base, false).unwrap();
+
+        let feature = repo.revparse_single("feature").unwrap().peel_to_commit().unwrap();
+        let merged = repo.revparse_single("merged").unwrap().peel_to_commit().unwrap();
+        let oldest = oldest_unique_commit(&repo, &feature, "main").unwrap().map(|commit| commit.summary().unwrap().to_string());
+        let fully_merged = oldest_unique_commit(&repo, &merged, "main").unwrap().map(|commit| commit.id());
+        let base_name = default_base(&repo).unwrap();
+        let missing_base = oldest_unique_commit(&repo, &feature, "no-such-base").is_err();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(oldest.as_deref(), Some("start"));
+        assert_eq!(fully_merged, None);
+        assert_eq!(base_name, "main");
+        assert!(missing_base);
+    }
+}
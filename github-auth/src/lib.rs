@@ -0,0 +1,158 @@
+use eyre::{Result, eyre};
+use log::debug;
+use reqwest::header;
+use std::{env, fs};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+pub const DEFAULT_TOKEN_PATH: &str = "~/.config/github/tokens";
+pub const DEFAULT_API_VERSION: &str = "2022-11-28";
+
+// lookup order: --token, an explicit <token-path>/<name>, the name's token-map entry,
+// the default <token-path>/<name>, the token-map default, GITHUB_TOKEN
+pub fn read_token(token: Option<&str>, token_map: &str, token_path: Option<&str>, name: &str) -> Result<Option<String>> {
+    if let Some(token) = token {
+        return Ok(Some(token.trim().to_string()));
+    }
+
+    let read_token_file = |token_path: &str| {
+        let token_file_path = PathBuf::from(shellexpand::tilde(token_path).to_string()).join(name);
+        debug!("Looking for a token in {}", token_file_path.display());
+        fs::read_to_string(token_file_path).ok().map(|token| token.trim().to_string())
+    };
+    if let Some(token) = token_path.and_then(read_token_file) {
+        return Ok(Some(token));
+    }
+
+    let token_map = fs::read_to_string(shellexpand::tilde(token_map).as_ref())
+        .map(|content| parse_token_map(&content))
+        .unwrap_or_default();
+    if let Some(source) = token_map.get(name) {
+        return read_token_source(source).map(Some);
+    }
+
+    if let Some(token) = token_path.is_none().then(|| read_token_file(DEFAULT_TOKEN_PATH)).flatten() {
+        return Ok(Some(token));
+    }
+    if let Some(source) = token_map.get("default") {
+        return read_token_source(source).map(Some);
+    }
+
+    Ok(env::var("GITHUB_TOKEN").ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty()))
+}
+
+pub fn parse_token_map(content: &str) -> HashMap<String, String> {
+    content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (name, source) = line.split_once('=')?;
+            Some((name.trim().to_string(), source.trim().to_string()))
+        })
+        .collect()
+}
+
+// a source is either a token file or, with a leading '$', an environment variable
+pub fn read_token_source(source: &str) -> Result<String> {
+    let token = match source.strip_prefix('$') {
+        Some(var) => env::var(var).map_err(|_| eyre!("Token variable {} is not set", var))?,
+        None => fs::read_to_string(shellexpand::tilde(source).as_ref())
+            .map_err(|e| eyre!("Failed to read token file {}: {}", source, e))?,
+    };
+    Ok(token.trim().to_string())
+}
+
+// user_agent should name the tool and its version; GitHub throttles generic agents more aggressively
+pub fn build_headers(token: Option<&str>, user_agent: &str, api_version: &str) -> Result<header::HeaderMap> {
+    let mut headers = header::HeaderMap::new();
+
+    // Bearer is accepted for classic PATs as well as fine-grained and installation tokens
+    if let Some(token) = token {
+        let auth_value = format!("Bearer {}", token);
+        headers.insert(header::AUTHORIZATION, header::HeaderValue::from_str(&auth_value)
+            .map_err(|e| eyre!("Failed to parse 'Authorization' header value: {}", e))?);
+    }
+    headers.insert(header::USER_AGENT, header::HeaderValue::from_str(user_agent)
+        .map_err(|e| eyre!("Failed to parse 'User-Agent' header value: {}", e))?);
+    headers.insert(header::ACCEPT, header::HeaderValue::from_static("application/vnd.github+json"));
+    headers.insert("X-GitHub-Api-Version", header::HeaderValue::from_str(api_version)
+        .map_err(|e| eyre!("Failed to parse 'X-GitHub-Api-Version' header value: {}", e))?);
+
+    debug!("Built headers for {} (API version {})", user_agent, api_version);
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_token_map() {
+        let map = parse_token_map("\
+# org = token-file or $VAR
+acme = ~/.config/github/acme-token
+  other=$OTHER_TOKEN
+default = /etc/github-token
+not a mapping
+");
+        assert_eq!(map.len(), 3);
+        assert_eq!(map["acme"], "~/.config/github/acme-token");
+        assert_eq!(map["other"], "$OTHER_TOKEN");
+        assert_eq!(map["default"], "/etc/github-token");
+    }
+
+    #[test]
+    fn test_read_token_source() {
+        let root = env::temp_dir().join(format!("github-auth-source-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("token"), "file-token\n").unwrap();
+
+        let from_file = read_token_source(root.join("token").to_str().unwrap());
+        let missing_file = read_token_source(root.join("missing").to_str().unwrap());
+        let missing_var = read_token_source("$GITHUB_AUTH_TEST_UNSET_VARIABLE");
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(from_file.unwrap(), "file-token");
+        assert!(missing_file.is_err());
+        assert!(missing_var.unwrap_err().to_string().contains("GITHUB_AUTH_TEST_UNSET_VARIABLE"));
+    }
+
+    #[test]
+    fn test_read_token_lookup_order() {
+        let root = env::temp_dir().join(format!("github-auth-tokens-{}", std::process::id()));
+        let explicit = root.join("explicit");
+        fs::create_dir_all(&explicit).unwrap();
+        fs::write(explicit.join("acme"), "explicit-token\n").unwrap();
+        fs::write(root.join("acme-mapped"), "mapped-token\n").unwrap();
+        let token_map = root.join("token-map");
+        fs::write(&token_map, format!("acme = {}\ndefault = {}\n", root.join("acme-mapped").display(), root.join("acme-mapped").display())).unwrap();
+
+        let token_map = token_map.to_str().unwrap();
+        let explicit = explicit.to_str().unwrap();
+        let flag = read_token(Some("flag-token"), token_map, Some(explicit), "acme").unwrap();
+        let from_path = read_token(None, token_map, Some(explicit), "acme").unwrap();
+        let from_map = read_token(None, token_map, Some(root.to_str().unwrap()), "acme").unwrap();
+        let from_default = read_token(None, token_map, Some(explicit), "other").unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(flag.as_deref(), Some("flag-token"));
+        assert_eq!(from_path.as_deref(), Some("explicit-token"));
+        assert_eq!(from_map.as_deref(), Some("mapped-token"));
+        assert_eq!(from_default.as_deref(), Some("mapped-token"));
+    }
+
+    #[test]
+    fn test_build_headers() {
+        let headers = build_headers(Some("secret"), "reposlug/v1.2.3", DEFAULT_API_VERSION).unwrap();
+        assert_eq!(headers[header::AUTHORIZATION], "Bearer secret");
+        assert_eq!(headers[header::USER_AGENT], "reposlug/v1.2.3");
+        assert_eq!(headers[header::ACCEPT], "application/vnd.github+json");
+        assert_eq!(headers["X-GitHub-Api-Version"], "2022-11-28");
+
+        let anonymous = build_headers(None, "reposlug/v1.2.3", DEFAULT_API_VERSION).unwrap();
+        assert!(!anonymous.contains_key(header::AUTHORIZATION));
+        assert!(build_headers(Some("bad\ntoken"), "reposlug", DEFAULT_API_VERSION).is_err());
+    }
+}
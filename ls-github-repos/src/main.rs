@@ -1,13 +1,19 @@
 use clap::{Parser, ValueEnum};
-use reqwest::{Client, header};
-use serde_json::Value;
-use tokio;
+use github_auth::{build_headers, read_token, DEFAULT_API_VERSION, DEFAULT_TOKEN_PATH};
+use reqwest::{Client, Response, StatusCode, header};
+use serde_json::{json, Value};
 use eyre::{Result, eyre};
 use std::{fs, fmt};
+#[cfg(unix)]
+use std::os::unix::fs::DirBuilderExt;
 use std::path::PathBuf;
-use shellexpand;
+use std::sync::Arc;
+use chrono::{DateTime, NaiveDate, Utc};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 use log::debug;
-use env_logger;
+
+const PER_PAGE: usize = 100;
 
 mod built_info {
     include!(concat!(env!("OUT_DIR"), "/git_describe.rs"));
@@ -21,20 +27,93 @@ mod built_info {
 #[command(arg_required_else_help = true)]
 struct Cli {
     /// Supply the GitHub organization or user name
-    #[clap(value_parser)]
-    name: String,
+    #[clap(value_parser, required_unless_present = "init_config")]
+    name: Option<String>,
+
+    /// Path to the directory containing the GitHub tokens; when given, its token wins over --token-map [default: ~/.config/github/tokens]
+    #[clap(short, long)]
+    token_path: Option<String>,
+
+    /// File of 'org = token-file' or 'org = $ENV_VAR' lines; a 'default' entry covers unlisted orgs
+    #[clap(long, default_value = "~/.config/github/token-map")]
+    token_map: String,
 
-    /// Path to the directory containing the GitHub tokens
-    #[clap(short, long, default_value = "~/.config/github/tokens")]
-    token_path: String,
+    /// GitHub token to use instead of the token files, e.g. a GitHub App installation token.
+    /// GITHUB_TOKEN is only the last fallback, after every token file; pass --token "$GITHUB_TOKEN" to make it win
+    #[clap(long, value_name = "TOKEN")]
+    token: Option<String>,
 
-    /// The type of repository owner, either 'user' or 'org'
-    #[clap(short, long, value_enum, default_value = "org")]
-    repo_type: RepoType,
+    /// The type of repository owner, either 'user' or 'org' [default: detected via the API]
+    #[clap(short, long, value_enum)]
+    repo_type: Option<RepoType>,
 
     /// Include archived repositories
     #[clap(short, long, action = clap::ArgAction::SetTrue)]
     archived: bool,
+
+    /// Field to sort the repositories by
+    #[clap(short, long, value_enum, default_value = "name")]
+    sort: SortKey,
+
+    /// Sort order
+    #[clap(short, long, value_enum, default_value = "asc")]
+    order: SortOrder,
+
+    /// Show each repository's size (KB) alongside its name
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    size: bool,
+
+    /// Show each repository's description
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    description: bool,
+
+    /// Show each repository's homepage
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    homepage: bool,
+
+    /// Only include repositories of at least this size (KB)
+    #[clap(long, value_name = "KB")]
+    min_size: Option<u64>,
+
+    /// Only include repositories of at most this size (KB)
+    #[clap(long, value_name = "KB")]
+    max_size: Option<u64>,
+
+    /// Look up each repository's latest release, or its newest tag's commit when it has none, and show its age in days ("never" if neither)
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    since_tag: bool,
+
+    /// Only include repositories with no release since DATE (YYYY-MM-DD); implies --since-tag
+    #[clap(long, value_name = "DATE")]
+    no_release_before: Option<NaiveDate>,
+
+    /// GitHub REST API version sent as X-GitHub-Api-Version
+    #[clap(long, default_value = DEFAULT_API_VERSION)]
+    api_version: String,
+
+    /// Maximum concurrent GitHub API requests; keep low to avoid secondary rate limits
+    #[clap(short = 'j', long, default_value_t = 4, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    threads: usize,
+
+    /// Skip validating the token against the API before listing
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    skip_preflight: bool,
+
+    /// Print only the number of matching repositories
+    #[clap(short, long, action = clap::ArgAction::SetTrue, conflicts_with = "json")]
+    count: bool,
+
+    /// Create the token directory (mode 700) if missing, print its path and exit
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    init_config: bool,
+
+    /// Print the repositories as a JSON array
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    json: bool,
+
+    /// Terminate each line with NUL instead of newline, for xargs -0
+    #[clap(short = '0', long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["count", "json"])]
+    print0: bool,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -45,6 +124,82 @@ enum RepoType {
     Org,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum SortKey {
+    /// Creation time
+    Created,
+    /// Last update time
+    Updated,
+    /// Last push time
+    Pushed,
+    /// Full repository name
+    Name,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum SortOrder {
+    /// Ascending
+    Asc,
+    /// Descending
+    Desc,
+}
+
+#[derive(Debug)]
+struct Repo {
+    full_name: String,
+    created_at: String,
+    updated_at: String,
+    pushed_at: String,
+    size: u64,
+    description: String,
+    homepage: String,
+    released_at: Option<DateTime<Utc>>,
+}
+
+impl Repo {
+    fn from_json(repo: &Value) -> Option<Self> {
+        let field = |key: &str| repo[key].as_str().unwrap_or_default().to_owned();
+        Some(Repo {
+            full_name: repo["full_name"].as_str()?.to_owned(),
+            created_at: field("created_at"),
+            updated_at: field("updated_at"),
+            pushed_at: field("pushed_at"),
+            size: repo["size"].as_u64().unwrap_or_default(),
+            description: field("description"),
+            homepage: field("homepage"),
+            released_at: None,
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "full_name": self.full_name,
+            "created_at": self.created_at,
+            "updated_at": self.updated_at,
+            "pushed_at": self.pushed_at,
+            "size": self.size,
+            "description": self.description,
+            "homepage": self.homepage,
+        })
+    }
+
+    fn sort_key(&self, key: SortKey) -> &str {
+        match key {
+            SortKey::Created => &self.created_at,
+            SortKey::Updated => &self.updated_at,
+            SortKey::Pushed => &self.pushed_at,
+            SortKey::Name => &self.full_name,
+        }
+    }
+
+    fn release_age(&self, now: DateTime<Utc>) -> String {
+        match self.released_at {
+            Some(released_at) => (now - released_at).num_days().to_string(),
+            None => "never".to_string(),
+        }
+    }
+}
+
 impl fmt::Display for RepoType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", match self {
@@ -59,64 +214,423 @@ async fn main() -> Result<()> {
     env_logger::init();
     let args = Cli::parse();
 
-    let expanded_token_path = shellexpand::tilde(&args.token_path).to_string();
-    let token_path = PathBuf::from(expanded_token_path);
-    let token_file_path = token_path.join(&args.name);
+    if args.init_config {
+        return init_config(args.token_path.as_deref().unwrap_or(DEFAULT_TOKEN_PATH));
+    }
+    let name = args.name.clone().ok_or_else(|| eyre!("name is required"))?;
 
-    let token = fs::read_to_string(token_file_path)
-        .map_err(|e| eyre!("Failed to read token file: {}", e))?
-        .trim().to_string();
+    let token = read_token(args.token.as_deref(), &args.token_map, args.token_path.as_deref(), &name)?
+        .ok_or_else(|| eyre!("No token for '{}': no token file, no token-map entry, and GITHUB_TOKEN is unset", name))?;
 
     debug!("Trimmed token: '{}'", token);
 
-    let repo_names = ls_github_repos(args.repo_type, &args.name, args.archived, &token).await?;
-    for repo_name in repo_names {
-        println!("{}", repo_name);
+    let client = Client::new();
+    let headers = build_headers(Some(&token), &format!("ls-github-repos/{}", built_info::GIT_DESCRIBE), &args.api_version)?;
+
+    if !args.skip_preflight {
+        verify_token(&client, &headers).await?;
+    }
+
+    let repo_type = match args.repo_type {
+        Some(repo_type) => repo_type,
+        None => determine_repo_type(&client, &headers, &name).await?,
+    };
+    debug!("Using repo type: {:?}", repo_type);
+
+    let since_tag = args.since_tag || args.no_release_before.is_some();
+
+    // the owner's metadata counts every repo, so it only answers --count when nothing is filtered out
+    if args.count && args.archived && args.min_size.is_none() && args.max_size.is_none() && !since_tag {
+        if let Some(count) = count_github_repos(&client, &headers, repo_type, &name).await? {
+            println!("{}", count);
+            return Ok(());
+        }
+    }
+
+    let mut repos = ls_github_repos(&client, &headers, repo_type, &name, args.archived, args.threads).await?;
+    repos.retain(|repo| {
+        args.min_size.is_none_or(|min| repo.size >= min)
+            && args.max_size.is_none_or(|max| repo.size <= max)
+    });
+    if since_tag {
+        fetch_release_dates(&client, &headers, &mut repos, args.threads).await?;
+        if let Some(before) = args.no_release_before {
+            repos.retain(|repo| repo.released_at.is_none_or(|released_at| released_at.date_naive() < before));
+        }
+    }
+    sort_repos(&mut repos, args.sort, args.order);
+
+    let now = Utc::now();
+
+    if args.count {
+        println!("{}", repos.len());
+    } else if args.json {
+        let repos: Vec<Value> = repos.iter().map(|repo| {
+            let mut value = repo.to_json();
+            if since_tag {
+                value["released_at"] = json!(repo.released_at.map(|released_at| released_at.to_rfc3339()));
+                value["release_age_days"] = json!(repo.released_at.map(|released_at| (now - released_at).num_days()));
+            }
+            value
+        }).collect();
+        println!("{}", serde_json::to_string_pretty(&repos)?);
+    } else {
+        for repo in repos {
+            let mut columns = vec![repo.full_name.clone()];
+            if args.size {
+                columns.push(repo.size.to_string());
+            }
+            if args.description {
+                columns.push(repo.description.clone());
+            }
+            if args.homepage {
+                columns.push(repo.homepage.clone());
+            }
+            if since_tag {
+                columns.push(repo.release_age(now));
+            }
+            if args.print0 {
+                print!("{}\0", columns.join("\t"));
+            } else {
+                println!("{}", columns.join("\t"));
+            }
+        }
+    }
+    Ok(())
+}
+
+// tokens live one per file, named after the org or user they're for
+fn init_config(token_path: &str) -> Result<()> {
+    let token_dir = PathBuf::from(shellexpand::tilde(token_path).to_string());
+    if token_dir.exists() {
+        println!("{} already exists; add one file per org or user containing its token", token_dir.display());
+        return Ok(());
     }
+
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    // owner-only on unix; elsewhere the directory inherits the platform's default ACLs
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder.create(&token_dir)
+        .map_err(|e| eyre!("Failed to create {}: {}", token_dir.display(), e))?;
+    println!("Created {}; add one file per org or user containing its token", token_dir.display());
+
     Ok(())
 }
 
-async fn ls_github_repos(repo_type: RepoType, name: &str, archived: bool, token: &str) -> Result<Vec<String>> {
-    let client = Client::new();
-    let base_url = format!("https://api.github.com/{}/{}", repo_type, name);
-    let url = format!("{}/repos", base_url);
-    let mut headers = header::HeaderMap::new();
-
-    debug!("Setting headers with token: '{}'", token);
-    let auth_value = format!("token {}", token);
-    headers.insert("Authorization", header::HeaderValue::from_str(&auth_value)
-        .map_err(|e| eyre!("Failed to parse 'Authorization' header value: {}", e))?);
-    headers.insert("User-Agent", header::HeaderValue::from_static("reqwest"));
-    headers.insert("Accept", header::HeaderValue::from_static("application/vnd.github.v3+json"));
-
-    debug!("Headers set successfully: {:?}", headers);
-
-    let mut repo_names = Vec::new();
-    let mut page = 1;
-
-    loop {
-        let response = client.get(&url)
-            .headers(headers.clone())
-            .query(&[("page", page.to_string()), ("per_page", "100".to_string())])
-            .send()
-            .await?
-            .json::<Vec<Value>>()
-            .await?;
-
-        if response.is_empty() {
-            break;
+async fn verify_token(client: &Client, headers: &header::HeaderMap) -> Result<()> {
+    let response = client.get("https://api.github.com/rate_limit")
+        .headers(headers.clone())
+        .send()
+        .await?;
+
+    match response.status() {
+        StatusCode::UNAUTHORIZED => return Err(eyre!("GitHub token is invalid or expired")),
+        status if !status.is_success() => return Err(eyre!("GitHub token check failed: {}", status)),
+        _ => {}
+    }
+
+    // classic tokens advertise their scopes; fine-grained and app tokens don't send the header
+    if let Some(scopes) = response.headers().get("x-oauth-scopes").and_then(|v| v.to_str().ok()) {
+        let scopes: Vec<&str> = scopes.split(',').map(str::trim).collect();
+        if !scopes.contains(&"repo") {
+            eprintln!("Warning: token lacks the 'repo' scope (has: {}); private repositories will be missing", scopes.join(", "));
         }
+    }
 
-        for repo in response {
-            if archived || !repo["archived"].as_bool().unwrap_or(false) {
-                if let Some(repo_name) = repo["full_name"].as_str() {
-                    repo_names.push(repo_name.to_owned());
-                }
-            }
+    let limits: Value = read_json(response).await?;
+    let core = &limits["resources"]["core"];
+    if let (Some(remaining), Some(limit)) = (core["remaining"].as_u64(), core["limit"].as_u64()) {
+        eprintln!("GitHub rate limit: {}/{} requests remaining", remaining, limit);
+        if remaining == 0 {
+            return Err(eyre!("GitHub rate limit exhausted"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn determine_repo_type(client: &Client, headers: &header::HeaderMap, name: &str) -> Result<RepoType> {
+    let url = format!("https://api.github.com/users/{}", name);
+    let response = client.get(&url)
+        .headers(headers.clone())
+        .send()
+        .await?;
+
+    match response.status() {
+        StatusCode::NOT_FOUND => return Err(eyre!("No GitHub user or org named '{}' exists", name)),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            return Err(eyre!("Token lacks access to look up '{}' ({}); pass --repo-type to skip detection", name, response.status()));
         }
-        page += 1;
+        status if !status.is_success() => return Err(eyre!("Failed to look up '{}': {}", name, status)),
+        _ => {}
+    }
+
+    let account: Value = read_json(response).await?;
+    match account["type"].as_str() {
+        Some("User") => Ok(RepoType::User),
+        Some("Organization") => Ok(RepoType::Org),
+        other => Err(eyre!("'{}' is an unsupported account type ({}); pass --repo-type explicitly", name, other.unwrap_or("missing"))),
+    }
+}
+
+async fn count_github_repos(client: &Client, headers: &header::HeaderMap, repo_type: RepoType, name: &str) -> Result<Option<u64>> {
+    let url = format!("https://api.github.com/{}/{}", repo_type, name);
+    let response = client.get(&url)
+        .headers(headers.clone())
+        .send()
+        .await?
+        .error_for_status()?;
+    let owner: Value = read_json(response).await?;
+
+    // private counts are only visible to members; fall back to enumeration without them
+    match (owner["public_repos"].as_u64(), owner["total_private_repos"].as_u64()) {
+        (Some(public), Some(private)) => Ok(Some(public + private)),
+        _ => Ok(None),
+    }
+}
+
+async fn ls_github_repos(client: &Client, headers: &header::HeaderMap, repo_type: RepoType, name: &str, archived: bool, threads: usize) -> Result<Vec<Repo>> {
+    let url = format!("https://api.github.com/{}/{}/repos", repo_type, name);
+
+    // the first page's Link header says how many pages follow; a short page or no Link means it was the only one
+    let (first, last_page) = fetch_page(client, &url, headers, 1).await?;
+    let last_page = if first.len() < PER_PAGE { 1 } else { last_page.unwrap_or(1) };
+    debug!("{} has {} page(s) of repos", url, last_page);
+
+    let semaphore = Arc::new(Semaphore::new(threads));
+    let mut requests = JoinSet::new();
+    for page in 2..=last_page {
+        let (client, url, headers, semaphore) = (client.clone(), url.clone(), headers.clone(), semaphore.clone());
+        requests.spawn(async move {
+            let _permit = semaphore.acquire_owned().await?;
+            fetch_page(&client, &url, &headers, page).await.map(|(repos, _)| (page, repos))
+        });
+    }
+
+    let mut pages = vec![(1, first)];
+    while let Some(result) = requests.join_next().await {
+        pages.push(result??);
+    }
+    pages.sort_by_key(|(page, _)| *page);
+
+    Ok(pages.into_iter()
+        .flat_map(|(_, repos)| repos)
+        .filter(|repo| archived || !repo["archived"].as_bool().unwrap_or(false))
+        .filter_map(|repo| Repo::from_json(&repo))
+        .collect())
+}
+
+async fn fetch_page(client: &Client, url: &str, headers: &header::HeaderMap, page: usize) -> Result<(Vec<Value>, Option<usize>)> {
+    debug!("Fetching page {} of {}", page, url);
+    let response = client.get(url)
+        .headers(headers.clone())
+        .query(&[("page", page.to_string()), ("per_page", PER_PAGE.to_string())])
+        .send()
+        .await?;
+    let last_page = response.headers()
+        .get(header::LINK)
+        .and_then(|link| link.to_str().ok())
+        .and_then(parse_last_page);
+
+    // errors such as bad credentials arrive as a JSON object with a message
+    match read_json(response).await? {
+        Value::Array(repos) => Ok((repos, last_page)),
+        other => Err(eyre!("Expected a list of repos from {}: {}", url, snippet(&other.to_string()))),
+    }
+}
+
+// <https://api.github.com/...&page=2>; rel="next", <https://api.github.com/...&page=7>; rel="last"
+fn parse_last_page(link: &str) -> Option<usize> {
+    let last = link.split(',').find(|part| part.contains(r#"rel="last""#))?;
+    let url = last.split_once('<')?.1.split_once('>')?.0;
+    let url = reqwest::Url::parse(url).ok()?;
+    let page = url.query_pairs().find(|(key, _)| key == "page")?.1;
+    page.parse().ok()
+}
+
+// abuse-detection and SSO pages come back as HTML; name the problem instead of dumping the page
+async fn read_json(response: Response) -> Result<Value> {
+    let status = response.status();
+    let url = response.url().clone();
+    let content_type = response.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("unknown")
+        .to_string();
+    let body = String::from_utf8_lossy(&response.bytes().await?).into_owned();
+    parse_json(url.as_str(), status, &content_type, &body)
+}
+
+fn parse_json(url: &str, status: StatusCode, content_type: &str, body: &str) -> Result<Value> {
+    if !content_type.contains("json") {
+        let hint = if content_type.contains("html") { ", likely a rate-limit or login page" } else { "" };
+        return Err(eyre!("Expected JSON from {} but received {} ({}){}: {}", url, content_type, status, hint, snippet(body)));
+    }
+
+    serde_json::from_str(body)
+        .map_err(|e| eyre!("Invalid JSON from {} ({}) at line {} column {}: {}", url, status, e.line(), e.column(), snippet(body)))
+}
+
+fn snippet(body: &str) -> String {
+    const MAX_CHARS: usize = 200;
+    let body = body.trim();
+    if body.chars().count() <= MAX_CHARS {
+        body.to_string()
+    } else {
+        let kept: String = body.chars().take(MAX_CHARS).collect();
+        format!("{}… ({} bytes total)", kept, body.len())
+    }
+}
+
+async fn fetch_release_dates(client: &Client, headers: &header::HeaderMap, repos: &mut [Repo], threads: usize) -> Result<()> {
+    let semaphore = Arc::new(Semaphore::new(threads));
+    let mut requests = JoinSet::new();
+    for (index, repo) in repos.iter().enumerate() {
+        let (client, headers, semaphore) = (client.clone(), headers.clone(), semaphore.clone());
+        let full_name = repo.full_name.clone();
+        requests.spawn(async move {
+            let _permit = semaphore.acquire_owned().await?;
+            let released_at = match latest_release(&client, &full_name, &headers).await? {
+                Some(released_at) => Some(released_at),
+                None => latest_tag(&client, &full_name, &headers).await?,
+            };
+            Ok::<_, eyre::Report>((index, released_at))
+        });
     }
 
-    repo_names.sort_unstable();
-    Ok(repo_names)
+    while let Some(result) = requests.join_next().await {
+        let (index, released_at) = result??;
+        repos[index].released_at = released_at;
+    }
+    Ok(())
+}
+
+async fn latest_release(client: &Client, full_name: &str, headers: &header::HeaderMap) -> Result<Option<DateTime<Utc>>> {
+    // repos that never published a release answer 404
+    let Some(release) = get_json(client, &format!("https://api.github.com/repos/{}/releases/latest", full_name), headers).await? else {
+        return Ok(None);
+    };
+    let published = release["published_at"].as_str().or_else(|| release["created_at"].as_str());
+    published.map(parse_timestamp).transpose()
+}
+
+// plenty of repos only push tags; the first one GitHub lists is dated by the commit it points at
+async fn latest_tag(client: &Client, full_name: &str, headers: &header::HeaderMap) -> Result<Option<DateTime<Utc>>> {
+    let Some(tags) = get_json(client, &format!("https://api.github.com/repos/{}/tags?per_page=1", full_name), headers).await? else {
+        return Ok(None);
+    };
+    let Some(commit_url) = tags[0]["commit"]["url"].as_str() else {
+        return Ok(None);
+    };
+    let Some(commit) = get_json(client, commit_url, headers).await? else {
+        return Ok(None);
+    };
+    commit["commit"]["committer"]["date"].as_str().map(parse_timestamp).transpose()
+}
+
+// None when GitHub has nothing there: 404 for a missing release or tag, 409 for an empty repository
+async fn get_json(client: &Client, url: &str, headers: &header::HeaderMap) -> Result<Option<Value>> {
+    debug!("Fetching {}", url);
+    let response = client.get(url)
+        .headers(headers.clone())
+        .send()
+        .await?;
+
+    if matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::CONFLICT) {
+        return Ok(None);
+    }
+    read_json(response.error_for_status()?).await.map(Some)
+}
+
+fn parse_timestamp(timestamp: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(timestamp)?.with_timezone(&Utc))
+}
+
+fn sort_repos(repos: &mut [Repo], key: SortKey, order: SortOrder) {
+    repos.sort_by(|a, b| {
+        let ordering = a.sort_key(key).cmp(b.sort_key(key))
+            .then_with(|| a.full_name.cmp(&b.full_name));
+        match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(full_name: &str, updated_at: &str) -> Repo {
+        Repo::from_json(&json!({"full_name": full_name, "updated_at": updated_at})).unwrap()
+    }
+
+    #[test]
+    fn test_snippet_truncates_long_bodies() {
+        assert_eq!(snippet("  short body\n"), "short body");
+
+        let long = "x".repeat(250);
+        let cut = snippet(&long);
+        assert!(cut.starts_with(&"x".repeat(200)));
+        assert!(cut.ends_with("… (250 bytes total)"), "{}", cut);
+    }
+
+    #[test]
+    fn test_parse_json_reports_bad_bodies() {
+        let url = "https://api.github.com/orgs/acme/repos";
+        let value = parse_json(url, StatusCode::OK, "application/json; charset=utf-8", r#"[{"full_name": "acme/api"}]"#).unwrap();
+        assert_eq!(value[0]["full_name"], "acme/api");
+
+        let html = parse_json(url, StatusCode::FORBIDDEN, "text/html", "<html>rate limited</html>").unwrap_err().to_string();
+        assert!(html.contains("likely a rate-limit or login page"), "{}", html);
+        assert!(html.contains("<html>rate limited</html>"), "{}", html);
+
+        let truncated = parse_json(url, StatusCode::OK, "application/json", "[{\"full_name\":").unwrap_err().to_string();
+        assert!(truncated.contains("Invalid JSON from https://api.github.com/orgs/acme/repos (200 OK) at line 1"), "{}", truncated);
+    }
+
+    #[test]
+    fn test_parse_last_page() {
+        let link = r#"<https://api.github.com/organizations/42/repos?page=2&per_page=100>; rel="next", <https://api.github.com/organizations/42/repos?page=7&per_page=100>; rel="last""#;
+        assert_eq!(parse_last_page(link), Some(7));
+
+        let on_last = r#"<https://api.github.com/organizations/42/repos?page=6&per_page=100>; rel="prev", <https://api.github.com/organizations/42/repos?page=1&per_page=100>; rel="first""#;
+        assert_eq!(parse_last_page(on_last), None);
+    }
+
+    #[test]
+    fn test_repo_from_json_null_description() {
+        let parsed = Repo::from_json(&json!({
+            "full_name": "acme/api",
+            "description": null,
+            "homepage": null,
+            "size": 42,
+        })).unwrap();
+        assert_eq!(parsed.description, "");
+        assert_eq!(parsed.homepage, "");
+        assert_eq!(parsed.size, 42);
+
+        assert!(Repo::from_json(&json!({"description": "no name"})).is_none());
+    }
+
+    #[test]
+    fn test_sort_repos() {
+        let names = |repos: &[Repo]| repos.iter().map(|repo| repo.full_name.clone()).collect::<Vec<_>>();
+        let mut repos = vec![
+            repo("acme/web", "2024-03-01T00:00:00Z"),
+            repo("acme/api", "2024-01-01T00:00:00Z"),
+            repo("acme/cli", "2024-03-01T00:00:00Z"),
+        ];
+
+        sort_repos(&mut repos, SortKey::Name, SortOrder::Asc);
+        assert_eq!(names(&repos), ["acme/api", "acme/cli", "acme/web"]);
+
+        // equal timestamps fall back to the name so the order is stable across runs
+        sort_repos(&mut repos, SortKey::Updated, SortOrder::Asc);
+        assert_eq!(names(&repos), ["acme/api", "acme/cli", "acme/web"]);
+        sort_repos(&mut repos, SortKey::Updated, SortOrder::Desc);
+        assert_eq!(names(&repos), ["acme/web", "acme/cli", "acme/api"]);
+    }
 }
@@ -1,7 +1,32 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use git2::Repository;
+use github_auth::{build_headers, read_token, DEFAULT_API_VERSION};
 use eyre::{Result, eyre};
+use log::debug;
 use regex::Regex;
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum Format {
+    /// owner/repo
+    Slug,
+    /// {host, owner, repo, slug, remote_url}
+    Json,
+}
+
+const REPO_CONFIG: &str = ".git-tools.toml";
+
+// only the shared `remote` key matters here; other tools' tables are ignored
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+struct RepoConfig {
+    remote: Option<String>,
+}
 
 mod built_info {
     include!(concat!(env!("OUT_DIR"), "/git_describe.rs"));
@@ -12,10 +37,34 @@ mod built_info {
 #[command(version = built_info::GIT_DESCRIBE)]
 #[command(author = "Scott A. Idler <scott.a.idler@gmail.com>")]
 struct Args {
-    #[clap(short, long)]
+    #[clap(short, long, help = "print diagnostics to stderr")]
     verbose: bool,
+    #[clap(short, long, value_enum, default_value = "slug", help = "output format")]
+    format: Format,
     #[clap(value_parser, help = "[default: .]")]
     directory: Option<String>, // Make this optional
+    #[clap(long, help = "verify the slug exists on GitHub and report its visibility")]
+    check: bool,
+    #[clap(long, help = "directory of GitHub tokens named by owner; when given, its token wins over --token-map [default: ~/.config/github/tokens]")]
+    token_path: Option<String>,
+    #[clap(long, default_value = "~/.config/github/token-map", help = "file of 'owner = token-file' or 'owner = $ENV_VAR' lines; a 'default' entry covers unlisted owners")]
+    token_map: String,
+    #[clap(long, value_name = "TOKEN", help = "GitHub token to use for --check instead of the token files")]
+    token: Option<String>,
+    #[clap(long, default_value = "origin", help = "remote to read the URL from; falls back to the only remote if missing")]
+    remote: String,
+    #[clap(long, value_name = "SLUG", help = "print the local checkout path(s) of SLUG found under the directory")]
+    resolve: Option<String>,
+    #[clap(long, requires = "resolve", help = "print every checkout --resolve finds instead of failing when there are several")]
+    all: bool,
+    #[clap(long, default_value_t = 3, help = "how many directory levels --resolve searches")]
+    max_depth: usize,
+    #[clap(long, conflicts_with = "resolve", help = "print every remote with its parsed slug, or why it failed to parse")]
+    list_remotes: bool,
+    #[clap(short = '0', long, help = "terminate output with NUL instead of newline, for xargs -0")]
+    print0: bool,
+    #[clap(long, help = "lowercase the slug so SSH and HTTPS remotes with different casing agree")]
+    normalize_slugs: bool,
 }
 
 fn main() -> Result<()> {
@@ -28,50 +77,290 @@ fn main() -> Result<()> {
     let directory = args.directory.unwrap_or_else(|| String::from("."));
 
     if args.verbose {
-        println!("Using directory: {}", directory);
+        eprintln!("Using directory: {}", directory);
+    }
+
+    if let Some(slug) = &args.resolve {
+        for path in resolve_slug(slug, Path::new(&directory), &args.remote, args.max_depth, args.all)? {
+            if args.print0 {
+                print!("{}\0", path.display());
+            } else {
+                println!("{}", path.display());
+            }
+        }
+        return Ok(());
     }
 
     // Open the repository from the specified directory
     let repo = Repository::discover(&directory)?;
-    let remote = repo.find_remote("origin")?;
-    let remote_url = remote.url().ok_or_else(|| eyre!("Remote 'origin' URL not found"))?;
+    if args.list_remotes {
+        for line in list_remotes(&repo)? {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+    let remote = repo_remote(&repo, &args.remote)?;
+    let remote_url = find_remote_url(&repo, &remote)?;
 
     if args.verbose {
-        println!("Remote URL: {}", remote_url);
+        eprintln!("Remote URL: {}", remote_url);
     }
 
-    let repo_slug = parse_git_url(remote_url)?;
+    let mut parsed = parse_git_remote(&remote_url)?;
+    if args.normalize_slugs {
+        parsed.owner = parsed.owner.to_lowercase();
+        parsed.repo = parsed.repo.to_lowercase();
+    }
+    let repo_slug = parsed.slug();
 
-    println!("{}", repo_slug);
+    match args.format {
+        Format::Slug if args.print0 => print!("{}\0", repo_slug),
+        Format::Slug => println!("{}", repo_slug),
+        Format::Json => println!("{}", json!({
+            "host": parsed.host,
+            "forge": parsed.forge().as_str(),
+            "owner": parsed.owner,
+            "repo": parsed.repo,
+            "slug": repo_slug,
+            "remote_url": remote_url,
+        })),
+    }
+
+    if args.check {
+        if parsed.forge() != Forge::GitHub {
+            return Err(eyre!("--check only supports GitHub remotes; {} is on {}", repo_slug, parsed.host));
+        }
+        let token = read_token(args.token.as_deref(), &args.token_map, args.token_path.as_deref(), &parsed.owner)?;
+        check_slug(&repo_slug, token.as_deref())?;
+    }
 
     Ok(())
 }
 
+fn find_remote_url(repo: &Repository, name: &str) -> Result<String> {
+    let remote = match repo.find_remote(name) {
+        Ok(remote) => remote,
+        Err(_) => {
+            let remotes: Vec<String> = repo.remotes()?.iter().flatten().map(str::to_string).collect();
+            let only = match remotes.as_slice() {
+                [only] => only.clone(),
+                [] => return Err(eyre!("Remote '{}' not found; this repository has no remotes", name)),
+                _ => return Err(eyre!("Remote '{}' not found; available: {}", name, remotes.join(", "))),
+            };
+            debug!("Remote '{}' not found; using the only remote '{}'", name, only);
+            repo.find_remote(&only)?
+        }
+    };
+
+    remote.url()
+        .map(str::to_string)
+        .ok_or_else(|| eyre!("Remote '{}' URL not found", remote.name().unwrap_or(name)))
+}
+
+// diagnostic: a remote that doesn't parse is reported, not an error
+fn list_remotes(repo: &Repository) -> Result<Vec<String>> {
+    Ok(repo.remotes()?.iter().flatten().map(|name| {
+        let url = repo.find_remote(name).ok().and_then(|remote| remote.url().map(str::to_string));
+        match url {
+            Some(url) => match parse_git_url(&url) {
+                Ok(slug) => format!("{}\t{}\t{}", name, url, slug),
+                Err(e) => format!("{}\t{}\t({})", name, url, e),
+            },
+            None => format!("{}\t\t(no URL or not valid UTF-8)", name),
+        }
+    }).collect())
+}
+
+// scripts usually cd into the result, so more than one checkout is an error unless --all asks for them
+fn resolve_slug(slug: &str, directory: &Path, remote: &str, max_depth: usize, all: bool) -> Result<Vec<PathBuf>> {
+    let mut repos = Vec::new();
+    find_repos(directory, max_depth, &mut repos);
+
+    let slug = normalize_slug(slug, true);
+    let mut matches: Vec<PathBuf> = repos.into_iter()
+        .filter(|path| repo_slug(path, remote).is_some_and(|found| normalize_slug(&found, true) == slug))
+        .collect();
+    matches.sort();
+
+    match matches.len() {
+        0 => Err(eyre!("No checkout of {} found under {}", slug, directory.display())),
+        1 => Ok(matches),
+        _ if all => Ok(matches),
+        _ => {
+            let candidates: Vec<String> = matches.iter().map(|path| path.display().to_string()).collect();
+            Err(eyre!("{} is checked out in several places; pass --all or pick one of:\n  {}", slug, candidates.join("\n  ")))
+        }
+    }
+}
+
+fn find_repos(directory: &Path, depth: usize, repos: &mut Vec<PathBuf>) {
+    if directory.join(".git").exists() {
+        repos.push(directory.to_path_buf());
+        return;
+    }
+    if depth == 0 {
+        return;
+    }
+
+    let Ok(entries) = fs::read_dir(directory) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+        let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if is_dir && !is_hidden {
+            find_repos(&entry.path(), depth - 1, repos);
+        }
+    }
+}
+
+fn repo_slug(path: &Path, remote: &str) -> Option<String> {
+    let repo = Repository::open(path).ok()?;
+    let remote = repo_remote(&repo, remote).ok()?;
+    parse_git_url(&find_remote_url(&repo, &remote).ok()?).ok()
+}
+
+// a repo's .git-tools.toml names its remote over --remote, e.g. forks that track "upstream"
+fn repo_remote(repo: &Repository, remote: &str) -> Result<String> {
+    let Some(path) = repo.workdir().map(|root| root.join(REPO_CONFIG)) else {
+        return Ok(remote.to_string());
+    };
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(remote.to_string()),
+        Err(e) => return Err(eyre!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    let config: RepoConfig = toml::from_str(&content).map_err(|e| eyre!("Failed to parse {}: {}", path.display(), e))?;
+    Ok(config.remote.unwrap_or_else(|| remote.to_string()))
+}
+
+fn check_slug(slug: &str, token: Option<&str>) -> Result<()> {
+    let user_agent = format!("reposlug/{}", built_info::GIT_DESCRIBE);
+    let response = Client::new()
+        .get(format!("https://api.github.com/repos/{}", slug))
+        .headers(build_headers(token, &user_agent, DEFAULT_API_VERSION)?)
+        .send()?;
+    match response.status() {
+        StatusCode::OK => {
+            let repo: Value = response.json()?;
+            let visibility = repo["visibility"].as_str().unwrap_or("unknown");
+            match repo["full_name"].as_str() {
+                Some(full_name) if !full_name.eq_ignore_ascii_case(slug) => {
+                    eprintln!("{}: exists ({}), renamed to {}", slug, visibility, full_name);
+                }
+                _ => eprintln!("{}: exists ({})", slug, visibility),
+            }
+            Ok(())
+        }
+        StatusCode::NOT_FOUND => Err(eyre!("{}: not found on GitHub (deleted, renamed away, or token lacks access)", slug)),
+        status => Err(eyre!("{}: GitHub check failed with {}", slug, status)),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct GitRemote {
+    host: String,
+    owner: String,
+    repo: String,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Forge {
+    GitHub,
+    GitLab,
+    Bitbucket,
+    Other,
+}
+
+impl Forge {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Forge::GitHub => "github",
+            Forge::GitLab => "gitlab",
+            Forge::Bitbucket => "bitbucket",
+            Forge::Other => "other",
+        }
+    }
+}
+
+impl GitRemote {
+    fn slug(&self) -> String {
+        format!("{}/{}", self.owner, self.repo)
+    }
+
+    fn forge(&self) -> Forge {
+        match self.host.to_lowercase().as_str() {
+            "github.com" | "ssh.github.com" => Forge::GitHub,
+            "gitlab.com" => Forge::GitLab,
+            "bitbucket.org" => Forge::Bitbucket,
+            _ => Forge::Other,
+        }
+    }
+}
+
+// strip the trailing slashes and .git suffix that differ between remote URL forms
+fn normalize_slug(slug: &str, lowercase: bool) -> String {
+    let slug = slug.trim().trim_end_matches('/');
+    let slug = slug.strip_suffix(".git").unwrap_or(slug);
+    if lowercase {
+        slug.to_lowercase()
+    } else {
+        slug.to_string()
+    }
+}
+
 fn parse_git_url(url: &str) -> Result<String> {
+    parse_git_remote(url).map(|remote| remote.slug())
+}
+
+fn parse_git_remote(url: &str) -> Result<GitRemote> {
     let re = Regex::new(
         r"(?x)
-        ^(?:git|https?|ssh)://   # Match the protocol
-        (?:[^@]+@)?              # Match the user authentication if present
-        [^:/]+                   # Match the host (not capturing)
-        [:/]                     # Match the separator after the host
-        (?P<slug>[^/]+/[^/]+?)   # Capture the slug
+        ^(?:git|https?|ssh|git\+ssh)://  # Match the protocol
+        (?:[^@/]+@)?             # Match the user authentication if present
+        (?P<host>[^:/]+)         # Capture the host
+        (?::\d+)?                # Match the port, if present
+        /                        # Match the separator after the host
+        (?P<path>[^/].*?)        # Capture everything after the host
         (?:\.git)?               # Match the .git extension, if present
         $|                       # Alternation for the next pattern
-        ^git@                    # Match the git@ prefix
-        [^:/]+                   # Match the host (not capturing)
-        :(?P<slug_2>[^/]+/[^/]+?)  # Capture the slug
+        ^(?:[^@/]+@)?            # Match the scp-style user, e.g. git@
+        (?P<host_2>[^:/]+)       # Capture the host
+        :(?P<path_2>[^/].*?)     # Capture everything after the colon
         (?:\.git)?               # Match the .git extension, if present
         $"                       // End of line
     ).map_err(|_| eyre!("Invalid regex pattern"))?;
 
-    re.captures(url)
-        .and_then(|caps| caps.name("slug").or_else(|| caps.name("slug_2")).map(|m| m.as_str().to_string()))
-        .ok_or_else(|| eyre!("Failed to parse URL"))
+    let caps = re.captures(url.trim().trim_end_matches('/')).ok_or_else(|| eyre!("Failed to parse URL"))?;
+    let group = |name: &str, alt: &str| caps.name(name).or_else(|| caps.name(alt)).map(|m| m.as_str().to_string());
+
+    // GitLab subgroups nest arbitrarily deep, so the repo is the last segment and the owner is the rest
+    let (Some(host), Some(path)) = (group("host", "host_2"), group("path", "path_2")) else {
+        return Err(eyre!("Failed to parse URL"));
+    };
+    let remote = match path.rsplit_once('/') {
+        Some((owner, repo)) if !owner.is_empty() && !repo.is_empty() => GitRemote {
+            host,
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+        },
+        _ => return Err(eyre!("Failed to parse URL")),
+    };
+
+    // GitHub and Bitbucket have no nested owners; a deeper path is a web URL like owner/repo/tree/main
+    match remote.forge() {
+        Forge::GitHub | Forge::Bitbucket if remote.owner.contains('/') => {
+            Err(eyre!("Failed to parse URL: {} remotes are exactly owner/repo", remote.forge().as_str()))
+        }
+        _ => Ok(remote),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::env;
 
     #[test]
     fn test_parse_git_urls() {
@@ -86,5 +375,108 @@ mod tests {
             assert_eq!(parse_git_url(url).unwrap(), "repo/slug", "URL parsing failed for: {}", url);
         }
     }
-}
 
+    #[test]
+    fn test_parse_git_remote_components() {
+        let expected = GitRemote {
+            host: "github.com".to_string(),
+            owner: "owner".to_string(),
+            repo: "repo".to_string(),
+        };
+
+        assert_eq!(parse_git_remote("git@github.com:owner/repo.git").unwrap(), expected);
+        assert_eq!(parse_git_remote("https://user@github.com/owner/repo").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_normalize_slug_variants() {
+        let urls = vec![
+            "https://github.com/Owner/Repo",
+            "https://github.com/owner/repo.git",
+            "https://github.com/owner/repo/",
+            "https://github.com/owner/repo.git/",
+            "git@github.com:OWNER/repo.git",
+            "ssh://git@github.com/owner/REPO",
+        ];
+
+        for url in urls {
+            let slug = parse_git_url(url).unwrap();
+            assert_eq!(normalize_slug(&slug, true), "owner/repo", "normalization failed for: {}", url);
+        }
+        assert_eq!(normalize_slug("Owner/Repo.git/", false), "Owner/Repo");
+    }
+
+    #[test]
+    fn test_parse_other_forges() {
+        let cases = vec![
+            ("git@gitlab.com:group/subgroup/repo.git", "gitlab.com", "group/subgroup", Forge::GitLab),
+            ("https://gitlab.com/group/subgroup/deeper/repo", "gitlab.com", "group/subgroup/deeper", Forge::GitLab),
+            ("ssh://git@gitlab.com:2222/group/subgroup/repo.git", "gitlab.com", "group/subgroup", Forge::GitLab),
+            ("https://user@bitbucket.org/team/repo.git", "bitbucket.org", "team", Forge::Bitbucket),
+            ("git@bitbucket.org:team/repo.git", "bitbucket.org", "team", Forge::Bitbucket),
+            ("ssh://git@github.com:22/owner/repo", "github.com", "owner", Forge::GitHub),
+            ("git@git.example.com:owner/repo", "git.example.com", "owner", Forge::Other),
+        ];
+
+        for (url, host, owner, forge) in cases {
+            let remote = parse_git_remote(url).unwrap_or_else(|e| panic!("{}: {}", url, e));
+            assert_eq!((remote.host.as_str(), remote.owner.as_str(), remote.repo.as_str()), (host, owner, "repo"), "URL: {}", url);
+            assert_eq!(remote.forge(), forge, "URL: {}", url);
+        }
+
+        assert!(parse_git_remote("https://github.com/onlyowner").is_err());
+        assert!(parse_git_remote("https://github.com/owner/repo/tree/main").is_err());
+        assert!(parse_git_remote("git@github.com:owner/repo/extra.git").is_err());
+        assert!(parse_git_remote("https://bitbucket.org/team/repo/src/main").is_err());
+    }
+
+    fn init_repo(path: &Path, remotes: &[(&str, &str)]) {
+        let repo = Repository::init(path).unwrap();
+        for (name, url) in remotes {
+            repo.remote(name, url).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_resolve_slug() {
+        let root = env::temp_dir().join(format!("reposlug-resolve-{}", std::process::id()));
+        init_repo(&root.join("work/api"), &[("origin", "git@github.com:acme/api.git")]);
+        init_repo(&root.join("oss/api-fork"), &[("origin", "https://github.com/Acme/API")]);
+        init_repo(&root.join("work/web"), &[("origin", "git@github.com:acme/web.git")]);
+        init_repo(&root.join("deep/a/b/c/tool"), &[("origin", "git@github.com:acme/tool.git")]);
+
+        let one = resolve_slug("acme/web", &root, "origin", 3, false);
+        let several = resolve_slug("acme/api", &root, "origin", 3, false);
+        let all = resolve_slug("acme/api", &root, "origin", 3, true);
+        let none = resolve_slug("acme/missing", &root, "origin", 3, false);
+        let too_deep = resolve_slug("acme/tool", &root, "origin", 3, false);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(one.unwrap(), vec![root.join("work/web")]);
+        let err = several.unwrap_err().to_string();
+        assert!(err.contains(&root.join("work/api").display().to_string()), "{}", err);
+        assert!(err.contains(&root.join("oss/api-fork").display().to_string()), "{}", err);
+        assert_eq!(all.unwrap(), vec![root.join("oss/api-fork"), root.join("work/api")]);
+        assert!(none.unwrap_err().to_string().starts_with("No checkout of acme/missing"));
+        assert!(too_deep.is_err());
+    }
+
+    #[test]
+    fn test_list_remotes() {
+        let root = env::temp_dir().join(format!("reposlug-remotes-{}", std::process::id()));
+        init_repo(&root, &[
+            ("origin", "git@github.com:me/tool.git"),
+            ("upstream", "https://github.com/acme/tool"),
+            ("local", "/srv/git/tool"),
+        ]);
+
+        let lines = list_remotes(&Repository::open(&root).unwrap()).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines.contains(&"origin\tgit@github.com:me/tool.git\tme/tool".to_string()), "{:?}", lines);
+        assert!(lines.contains(&"upstream\thttps://github.com/acme/tool\tacme/tool".to_string()), "{:?}", lines);
+        let local = lines.iter().find(|line| line.starts_with("local\t/srv/git/tool\t(")).unwrap();
+        assert!(local.ends_with(')'), "{}", local);
+    }
+}
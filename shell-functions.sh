@@ -10,6 +10,8 @@ CLONE=$(print -r -- =clone)
 clone() {
     if [[ "$1" == (-h|--help|-v|--version) ]]; then
         eval $CLONE "$@"
+    elif [[ "$1" == (--org|--from-manifest|--init-config) || "$1" == *[\*\?\[]* ]]; then
+        $CLONE "$@"
     else
         cd $($CLONE "$1") || return
     fi
@@ -1,13 +1,26 @@
-use clap::Parser;
-use env_logger;
-use eyre::{Result, Context};
-use log::debug;
-use serde::Serialize;
-use serde_yaml;
-use std::collections::HashMap;
+use clap::{Parser, ValueEnum};
+use eyre::{Result, Context, eyre};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::{env, fs};
 use std::io::{self, Write};
-use std::process::{Command as SysCommand};
-use chrono::{Utc, NaiveDate};
+use std::path::{Path, PathBuf};
+use std::process::{Command as SysCommand, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc, NaiveDate};
+use git2::Repository;
+use serde_json::{json, Value};
+
+const DEFAULT_BOT_PATTERNS: [&str; 4] = [
+    "*[bot]",
+    "dependabot*",
+    "renovate*",
+    "github-actions*",
+];
+
+const REPO_CONFIG: &str = ".git-tools.toml";
 
 mod built_info {
     include!(concat!(env!("OUT_DIR"), "/git_describe.rs"));
@@ -22,8 +35,80 @@ struct Cli {
     #[arg(help = "Number of days to consider a branch stale.")]
     days: i64,
 
-    #[arg(long, help = "Git reference to check.", default_value = "refs/remotes/origin")]
-    ref_: String,
+    #[arg(long, help = "Git reference to check; use refs/heads for local branches. [default: refs/remotes/<remote>]")]
+    ref_: Option<String>,
+
+    #[arg(long, help = "Check both refs/heads and refs/remotes/<remote>, deduplicating branches found in both.", conflicts_with = "ref_")]
+    all_refs: bool,
+
+    #[arg(long, value_name = "YYYY-MM-DD", value_parser = parse_as_of, help = "Compute branch ages as of this date instead of now, for reproducible reports.")]
+    as_of: Option<i64>,
+
+    #[arg(long, value_name = "DAYS,...", value_delimiter = ',', value_parser = clap::value_parser!(i64).range(1..), default_value = "90,180,365", help = "Ascending day boundaries between the --format buckets age ranges.")]
+    bucket_edges: Vec<i64>,
+
+    #[arg(long, help = "Read refs and committer dates in-process with libgit2 instead of parsing git for-each-ref; faster with many refs.")]
+    libgit2: bool,
+
+    #[arg(long, value_enum, default_value = "yaml", help = "Output format.")]
+    format: Format,
+
+    #[arg(long, help = "Remote to fetch and report on; falls back to the only remote if this one is missing.", default_value = "origin")]
+    remote: String,
+
+    #[arg(long, help = "Fetch without --prune; faster, but branches deleted upstream still show as stale.")]
+    no_prune: bool,
+
+    #[arg(long, value_name = "SECS", help = "Give up on the fetch after this many seconds instead of hanging on an unreachable remote.")]
+    timeout: Option<u64>,
+
+    #[arg(long, value_name = "PATH", help = "File of 'from = to' lines mapping author names to a canonical name.")]
+    author_map: Option<PathBuf>,
+
+    #[arg(long, help = "Skip branches whose author looks like a bot (dependabot, renovate, *[bot], ...).")]
+    exclude_bots: bool,
+
+    #[arg(long = "bot", value_name = "PATTERN", help = "Additional bot author pattern for --exclude-bots; '*' may lead or trail.")]
+    bots: Vec<String>,
+
+    #[arg(long = "author", value_name = "NAME", help = "Only report branches by this author (case-insensitive substring); repeatable.")]
+    authors: Vec<String>,
+
+    #[arg(long, help = "Require --author to match the full author name exactly.")]
+    exact_author: bool,
+
+    #[arg(long, help = "Only report branches already merged into --base.", conflicts_with = "unmerged")]
+    merged: bool,
+
+    #[arg(long, help = "Only report branches not yet merged into --base.")]
+    unmerged: bool,
+
+    #[arg(long, value_name = "BRANCH", help = "Branch that --merged/--unmerged compare against, e.g. develop. [default: <remote>/HEAD]")]
+    base: Option<String>,
+
+    #[arg(long, value_name = "PATH", help = "Write a reviewable shell script of 'git push <remote> --delete' commands for branches whose <remote> tip is stale.")]
+    gen_delete_script: Option<PathBuf>,
+
+    #[arg(long, help = "Delete branches whose <remote> tip is stale from the remote; only prints the plan unless --yes is given.")]
+    delete: bool,
+
+    #[arg(long, requires = "delete", help = "Print the deletions --delete would run without running them (the default).")]
+    dry_run: bool,
+
+    #[arg(long, requires = "delete", conflicts_with = "dry_run", help = "Actually run the deletions planned by --delete.")]
+    yes: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum Format {
+    /// Branches grouped by author as YAML
+    Yaml,
+    /// Column-aligned table of author, branch and age
+    Table,
+    /// One aggregate JSON object (totals, per-author rollup, oldest branch) for dashboards
+    SummaryJson,
+    /// Branches grouped into age buckets (see --bucket-edges) as YAML
+    Buckets,
 }
 
 #[derive(Serialize, Debug)]
@@ -32,59 +117,610 @@ struct AuthorBranches {
     count: usize,
 }
 
+impl AuthorBranches {
+    fn max_days(&self) -> i64 {
+        self.branches.iter().flat_map(|branch| branch.values().copied()).max().unwrap_or_default()
+    }
+}
+
+// .git-tools.toml at the repo root; `remote` is shared by the tools, the rest lives under [stale-branches]
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+struct RepoConfig {
+    remote: Option<String>,
+    #[serde(rename = "stale-branches")]
+    stale_branches: StaleBranchesConfig,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+struct StaleBranchesConfig {
+    exclude_bots: Option<bool>,
+    bots: Vec<String>,
+    author_map: Option<PathBuf>,
+    base: Option<String>,
+}
+
 fn main() -> Result<()> {
     env_logger::init();
-    let args = Cli::parse();
+    let mut args = Cli::parse();
+    ensure_installed("git")?;
+    apply_repo_config(&mut args, load_repo_config(Path::new("."))?);
 
-    SysCommand::new("git")
-        .args(["fetch", "origin", "--prune"])
-        .output()
-        .wrap_err("Failed to prune local cache of git branches")?;
+    let remote = resolve_remote(&args.remote)?;
+    debug!("Using remote: {}", remote);
+
+    let mut fetch = SysCommand::new("git");
+    fetch.args(["fetch", &remote]);
+    if !args.no_prune {
+        fetch.arg("--prune");
+    }
+    fetch.stdout(Stdio::null()).stderr(Stdio::null());
+    // an unreachable remote shouldn't sink the report; fall back to the remote-tracking refs we already have
+    match wait_bounded(&mut fetch, args.timeout.map(Duration::from_secs)) {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!("Fetching {} failed ({}); reporting from existing remote-tracking refs", remote, status),
+        Err(e) => warn!("Fetching {} {}; reporting from existing remote-tracking refs", remote, e),
+    }
+
+    let remote_refs = format!("refs/remotes/{}", remote);
+    let refs = if args.all_refs {
+        vec!["refs/heads".to_string(), remote_refs]
+    } else {
+        vec![args.ref_.clone().unwrap_or(remote_refs)]
+    };
 
-    let branches = get_stale_branches(args.days, &args.ref_)?;
-    generate_yaml(&branches)?;
+    let mut branches = if args.libgit2 {
+        get_stale_branches_libgit2(Path::new("."), args.days, &refs, args.as_of)?
+    } else {
+        get_stale_branches(Path::new("."), args.days, &refs, args.as_of)?
+    };
+    if let Some(path) = &args.author_map {
+        let author_map = load_author_map(path)?;
+        apply_author_map(&mut branches, &author_map);
+    }
+    if args.exclude_bots {
+        let patterns: Vec<&str> = DEFAULT_BOT_PATTERNS.iter().copied()
+            .chain(args.bots.iter().map(String::as_str))
+            .collect();
+        branches.retain(|(_, _, author, _)| !is_bot(author, &patterns));
+    }
+    if !args.authors.is_empty() {
+        branches.retain(|(_, _, author, _)| matches_author(author, &args.authors, args.exact_author));
+    }
+    if args.merged || args.unmerged {
+        let base = resolve_base(Path::new("."), &remote, args.base.as_deref())?;
+        debug!("Comparing against base: {}", base);
+        let merged = merged_branches(Path::new("."), &base, &refs)?;
+        branches.retain(|(branch, _, _, _)| merged.contains(branch) == args.merged);
+    }
+    if args.gen_delete_script.is_some() || args.delete {
+        let default_branch = get_default_branch(&remote);
+        let deletions = deletion_plan(&branches, &remote, default_branch.as_deref());
+        if let Some(path) = &args.gen_delete_script {
+            write_delete_script(path, &remote, &deletions, args.days)?;
+        }
+        if args.delete {
+            delete_branches(&remote, &deletions, !args.yes)?;
+        }
+    }
+    match args.format {
+        Format::Yaml => generate_yaml(&branches)?,
+        Format::Table => generate_table(&branches)?,
+        Format::Buckets => {
+            check_bucket_edges(&args.bucket_edges)?;
+            generate_buckets(&branches, &args.bucket_edges)?
+        }
+        Format::SummaryJson => {
+            let summary = summarize(&branches, &remote, args.days, Utc::now().timestamp());
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+        }
+    }
 
     Ok(())
 }
 
-fn get_stale_branches(days: i64, ref_: &str) -> Result<Vec<(String, i64, String)>> {
+fn get_stale_branches(repo_dir: &Path, days: i64, refs: &[String], as_of: Option<i64>) -> Result<Vec<(String, i64, String, String)>> {
     let output = SysCommand::new("git")
-        .args(["for-each-ref", "--sort=-committerdate", ref_, "--format=%(committerdate:short) %(refname:short) %(committername)"])
+        .args(["for-each-ref", "--sort=-committerdate", "--format=%(committerdate:unix) %(refname) %(committername) %(committeremail)"])
+        .args(refs)
+        .current_dir(repo_dir)
         .output()
         .wrap_err("Failed to execute git command")?;
 
-    let current_time = Utc::now().timestamp();
+    let current_time = as_of.unwrap_or_else(|| Utc::now().timestamp());
     debug!("current_time: {}", current_time);
-    let result = String::from_utf8(output.stdout)?;
+    // a single branch or author name with invalid UTF-8 shouldn't abort the whole report
+    let result = String::from_utf8_lossy(&output.stdout);
+
+    let mut branches = parse_branches(&result, days, current_time);
+    apply_mailmap(repo_dir, &mut branches)?;
+    Ok(branches)
+}
+
+fn get_stale_branches_libgit2(repo_dir: &Path, days: i64, refs: &[String], as_of: Option<i64>) -> Result<Vec<(String, i64, String, String)>> {
+    let repo = Repository::discover(repo_dir).wrap_err("Failed to open repository")?;
+    let mailmap = repo.mailmap()?;
+    let current_time = as_of.unwrap_or_else(|| Utc::now().timestamp());
+
+    let mut found = Vec::new();
+    for reference in repo.references()? {
+        let reference = reference?;
+        let refname = String::from_utf8_lossy(reference.name_bytes()).into_owned();
+        // same prefix semantics as for-each-ref patterns
+        let wanted = refs.iter().any(|prefix| {
+            let prefix = prefix.trim_end_matches('/');
+            refname == prefix || refname.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'))
+        });
+        if !wanted {
+            continue;
+        }
+        let Some(branch) = branch_name(&refname) else {
+            continue;
+        };
+        let Ok(commit) = reference.peel_to_commit() else {
+            debug!("Skipping {}: does not point at a commit", refname);
+            continue;
+        };
+        let committer = commit.committer_with_mailmap(&mailmap)?;
+        found.push((branch, commit.committer().when().seconds(), String::from_utf8_lossy(committer.name_bytes()).into_owned(), refname));
+    }
+
+    // newest first, so deduping keeps each branch's latest commit like the for-each-ref path
+    found.sort_by(|(b1, t1, _, _), (b2, t2, _, _)| t2.cmp(t1).then_with(|| b1.cmp(b2)));
+    let mut seen = HashSet::new();
+    Ok(found.into_iter()
+        .filter(|(branch, _, _, _)| seen.insert(branch.clone()))
+        .map(|(branch, commit_time, author, refname)| (branch, (current_time - commit_time) / 86_400, author, refname))
+        .filter(|(_, days_since_commit, _, _)| *days_since_commit >= days)
+        .collect())
+}
 
-    let branches: Vec<(String, i64, String)> = result.lines()
+// for-each-ref ignores .mailmap, so canonicalize "Name <email>" contacts through check-mailmap
+fn apply_mailmap(repo_dir: &Path, branches: &mut [(String, i64, String, String)]) -> Result<()> {
+    let contacts: Vec<String> = branches.iter()
+        .map(|(_, _, author, _)| author.clone())
+        .filter(|author| author.ends_with('>'))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let mut canonical = HashMap::new();
+    if !contacts.is_empty() {
+        let output = SysCommand::new("git")
+            .arg("check-mailmap")
+            .args(&contacts)
+            .current_dir(repo_dir)
+            .output()
+            .wrap_err("Failed to execute git check-mailmap")?;
+        if output.status.success() {
+            canonical.extend(contacts.iter().cloned().zip(String::from_utf8_lossy(&output.stdout).lines().map(str::to_string)));
+        } else {
+            warn!("git check-mailmap failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+    }
+
+    for (_, _, author, _) in branches.iter_mut() {
+        let contact = canonical.get(author.as_str()).unwrap_or(author);
+        *author = strip_email(contact).to_string();
+    }
+
+    Ok(())
+}
+
+fn strip_email(contact: &str) -> &str {
+    match contact.rfind(" <") {
+        Some(idx) if contact.ends_with('>') => &contact[..idx],
+        _ => contact,
+    }
+}
+
+fn parse_branches(output: &str, days: i64, current_time: i64) -> Vec<(String, i64, String, String)> {
+    let mut seen = HashSet::new();
+
+    output.lines()
         .filter_map(|line| {
             let parts: Vec<&str> = line.split_whitespace().collect();
             if parts.len() < 3 { return None; }
             let date_str = parts[0];
-            let branch = parts[1].trim_start_matches("origin/").to_string();
+            let refname = parts[1];
+            let branch = branch_name(refname)?;
             let author = parts[2..].join(" ");
-            let commit_time = NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
-                .ok()?
-                .and_hms_opt(0, 0, 0)?
-                .and_utc().timestamp();
+            // unix timestamps are immune to log.date settings and locale
+            let Ok(commit_time) = date_str.parse::<i64>() else {
+                debug!("Skipping unparseable for-each-ref line: {}", line);
+                return None;
+            };
             let days_since_commit = (current_time - commit_time) / 86_400;
 
+            // input is sorted newest first, so the first sighting of a branch is its latest commit
+            if !seen.insert(branch.clone()) {
+                return None;
+            }
+
             if days_since_commit >= days {
-                Some((branch, days_since_commit, author))
+                Some((branch, days_since_commit, author, refname.to_string()))
             } else {
                 None
             }
         })
-        .collect();
+        .collect()
+}
 
-    Ok(branches)
+fn branch_name(refname: &str) -> Option<String> {
+    let name = if let Some(local) = refname.strip_prefix("refs/heads/") {
+        local
+    } else if let Some(remote) = refname.strip_prefix("refs/remotes/") {
+        remote.split_once('/')?.1
+    } else {
+        refname
+    };
+
+    // skip symbolic refs like origin/HEAD
+    if name == "HEAD" {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+fn parse_as_of(s: &str) -> Result<i64> {
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").wrap_err("Expected a date like 2024-01-31")?;
+    Ok(date.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc().timestamp()).unwrap_or_default())
+}
+
+fn check_bucket_edges(edges: &[i64]) -> Result<()> {
+    if edges.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(eyre!("Bucket edges must be ascending, e.g. 90,180,365"));
+    }
+    Ok(())
+}
+
+fn is_bot(author: &str, patterns: &[&str]) -> bool {
+    let author = author.to_lowercase();
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.to_lowercase();
+        match (pattern.strip_prefix('*'), pattern.strip_suffix('*')) {
+            (Some(_), Some(_)) => author.contains(pattern.trim_matches('*')),
+            (Some(suffix), None) => author.ends_with(suffix),
+            (None, Some(prefix)) => author.starts_with(prefix),
+            (None, None) => author == pattern,
+        }
+    })
+}
+
+fn matches_author(author: &str, wanted: &[String], exact: bool) -> bool {
+    if exact {
+        wanted.iter().any(|name| name == author)
+    } else {
+        let author = author.to_lowercase();
+        wanted.iter().any(|name| author.contains(&name.to_lowercase()))
+    }
+}
+
+fn load_repo_config(repo_dir: &Path) -> Result<RepoConfig> {
+    let Some(root) = Repository::discover(repo_dir).ok().and_then(|repo| repo.workdir().map(Path::to_path_buf)) else {
+        return Ok(RepoConfig::default());
+    };
+
+    let path = root.join(REPO_CONFIG);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(RepoConfig::default()),
+        Err(e) => return Err(e).wrap_err_with(|| format!("Failed to read {}", path.display())),
+    };
+    debug!("Using repo config {}", path.display());
+
+    let mut config: RepoConfig = toml::from_str(&content).wrap_err_with(|| format!("Failed to parse {}", path.display()))?;
+    if let Some(author_map) = &mut config.stale_branches.author_map {
+        *author_map = root.join(&author_map);
+    }
+    Ok(config)
+}
+
+// the repo's own settings win over flags, so maintainers can pin conventions for everyone; bot patterns add up
+fn apply_repo_config(args: &mut Cli, config: RepoConfig) {
+    let StaleBranchesConfig { exclude_bots, bots, author_map, base } = config.stale_branches;
+    if let Some(remote) = config.remote {
+        args.remote = remote;
+    }
+    if let Some(exclude_bots) = exclude_bots {
+        args.exclude_bots = exclude_bots;
+    }
+    args.bots.extend(bots);
+    if author_map.is_some() {
+        args.author_map = author_map;
+    }
+    if base.is_some() {
+        args.base = base;
+    }
+}
+
+fn resolve_remote(name: &str) -> Result<String> {
+    let output = SysCommand::new("git")
+        .arg("remote")
+        .output()
+        .map_err(|e| missing_program("git", e))?;
+    if !output.status.success() {
+        return Err(eyre!("git remote failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let remotes: Vec<String> = String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect();
+    pick_remote(name, &remotes)
+}
+
+fn pick_remote(name: &str, remotes: &[String]) -> Result<String> {
+    match remotes {
+        _ if remotes.iter().any(|remote| remote == name) => Ok(name.to_string()),
+        [only] => {
+            warn!("Remote '{}' not found; using the only remote '{}'", name, only);
+            Ok(only.clone())
+        }
+        [] => Err(eyre!("Remote '{}' not found; this repository has no remotes", name)),
+        _ => Err(eyre!("Remote '{}' not found; available: {}", name, remotes.join(", "))),
+    }
+}
+
+fn get_default_branch(remote: &str) -> Option<String> {
+    let output = SysCommand::new("git")
+        .args(["symbolic-ref", "--short", &format!("refs/remotes/{}/HEAD", remote)])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        debug!("Could not resolve {}/HEAD: {}", remote, String::from_utf8_lossy(&output.stderr).trim());
+        return None;
+    }
+
+    let head = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    head.strip_prefix(&format!("{}/", remote)).map(str::to_string)
+}
+
+fn ensure_installed(program: &str) -> Result<()> {
+    SysCommand::new(program)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|_| ())
+        .map_err(|e| missing_program(program, e))
+}
+
+// spawn reports a missing binary as a bare "No such file or directory"; name the program instead
+fn missing_program(program: &str, e: io::Error) -> eyre::Report {
+    if e.kind() == io::ErrorKind::NotFound {
+        eyre!("{} not found on PATH; please install {}", program, program)
+    } else {
+        eyre!("Failed to run {}: {}", program, e)
+    }
+}
+
+fn wait_bounded(command: &mut SysCommand, timeout: Option<Duration>) -> Result<ExitStatus> {
+    let mut child = command.spawn()
+        .map_err(|e| missing_program(&command.get_program().to_string_lossy(), e))?;
+    let Some(timeout) = timeout else {
+        return Ok(child.wait()?);
+    };
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(eyre!("timed out after {}s", timeout.as_secs()));
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+// an explicit base may name a remote branch or any local ref; a missing one is an error, not "nothing merged"
+fn resolve_base(repo_dir: &Path, remote: &str, base: Option<&str>) -> Result<String> {
+    let candidates = match base {
+        Some(base) => vec![format!("refs/remotes/{}/{}", remote, base), base.to_string()],
+        None => match get_default_branch(remote) {
+            Some(default_branch) => vec![format!("refs/remotes/{}/{}", remote, default_branch)],
+            None => return Err(eyre!("Could not resolve {}/HEAD; pass --base", remote)),
+        },
+    };
+
+    candidates.into_iter()
+        .find(|candidate| {
+            SysCommand::new("git")
+                .args(["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", candidate)])
+                .current_dir(repo_dir)
+                .output()
+                .is_ok_and(|output| output.status.success())
+        })
+        .ok_or_else(|| eyre!("Base branch '{}' does not exist", base.unwrap_or("HEAD")))
+}
+
+fn merged_branches(repo_dir: &Path, base: &str, refs: &[String]) -> Result<HashSet<String>> {
+    let output = SysCommand::new("git")
+        .args(["for-each-ref", &format!("--merged={}", base), "--format=%(refname)"])
+        .args(refs)
+        .current_dir(repo_dir)
+        .output()
+        .wrap_err("Failed to list merged branches")?;
+
+    if !output.status.success() {
+        return Err(eyre!("git for-each-ref --merged failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    // the base is trivially merged into itself; don't report it as such
+    let base_branch = branch_name(base);
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(branch_name)
+        .filter(|branch| Some(branch) != base_branch.as_ref())
+        .collect())
+}
+
+fn is_protected_branch(branch: &str, default_branch: Option<&str>) -> bool {
+    match default_branch {
+        Some(default_branch) => branch == default_branch,
+        // without <remote>/HEAD, err on the side of never deleting the usual defaults
+        None => matches!(branch, "main" | "master"),
+    }
+}
+
+// only a stale tip of <remote>'s own branch may be deleted there; a stale local branch says nothing about the remote one
+fn deletion_plan<'a>(branches: &'a [(String, i64, String, String)], remote: &str, default_branch: Option<&str>) -> Vec<&'a (String, i64, String, String)> {
+    branches.iter()
+        .filter(|(branch, _, _, refname)| {
+            let on_remote = refname.strip_prefix("refs/remotes/")
+                .and_then(|rest| rest.strip_prefix(remote))
+                .and_then(|rest| rest.strip_prefix('/'))
+                == Some(branch.as_str());
+            if !on_remote {
+                debug!("Not deleting {}: stale tip is {}, not {}/{}", branch, refname, remote, branch);
+            }
+            on_remote
+        })
+        .filter(|(branch, _, _, _)| !is_protected_branch(branch, default_branch))
+        .collect()
+}
+
+fn delete_command(remote: &str, branch: &str) -> String {
+    format!("git push {} --delete '{}'", remote, branch.replace('\'', "'\\''"))
+}
+
+fn delete_branches(remote: &str, deletions: &[&(String, i64, String, String)], dry_run: bool) -> Result<()> {
+    if dry_run {
+        eprintln!("Dry run: would delete {} branches (pass --yes to run):", deletions.len());
+    }
+
+    let mut failures = 0;
+    for (branch, age, author, _) in deletions {
+        eprintln!("{}  # {}, {} days", delete_command(remote, branch), author, age);
+        if dry_run {
+            continue;
+        }
+
+        let status = SysCommand::new("git")
+            .args(["push", remote, "--delete", branch])
+            .status()
+            .wrap_err_with(|| format!("Failed to delete branch {}", branch))?;
+        if !status.success() {
+            warn!("Failed to delete {}: {}", branch, status);
+            failures += 1;
+        }
+    }
+
+    if failures > 0 {
+        return Err(eyre!("Failed to delete {} of {} branches", failures, deletions.len()));
+    }
+
+    Ok(())
+}
+
+fn write_delete_script(path: &Path, remote: &str, deletions: &[&(String, i64, String, String)], days: i64) -> Result<()> {
+    let mut script = String::from("#!/bin/sh\n");
+    script.push_str(&format!("# Generated by stale-branches: {} branches with no commits in {} days.\n", deletions.len(), days));
+    script.push_str(&format!("# Only branches whose {} tip is stale are listed; local-only branches are left alone.\n", remote));
+    script.push_str(&format!("# Review before running; each line deletes a branch on {}.\n", remote));
+    for (branch, age, author, _) in deletions {
+        script.push_str(&format!("\n# {}, {} days\n", author, age));
+        script.push_str(&format!("{}\n", delete_command(remote, branch)));
+    }
+
+    fs::write(path, script).wrap_err_with(|| format!("Failed to write delete script to {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+            .wrap_err_with(|| format!("Failed to make {} executable", path.display()))?;
+    }
+    eprintln!("Wrote delete script to {}", path.display());
+
+    Ok(())
+}
+
+fn generate_table(branches: &[(String, i64, String, String)]) -> Result<()> {
+    let mut rows: Vec<&(String, i64, String, String)> = branches.iter().collect();
+    rows.sort_by(|(b1, d1, a1, _), (b2, d2, a2, _)| a1.cmp(a2).then(d2.cmp(d1)).then(b1.cmp(b2)));
+
+    let author_width = rows.iter().map(|(_, _, author, _)| author.chars().count()).chain([6]).max().unwrap_or_default();
+    let days_width = rows.iter().map(|(_, days, _, _)| days.to_string().len()).chain([4]).max().unwrap_or_default();
+    let branch_width = rows.iter().map(|(branch, _, _, _)| branch.chars().count()).chain([6]).max().unwrap_or_default();
+
+    // keep rows on one line when the terminal width is known; the branch column gives way
+    let branch_width = match terminal_width() {
+        Some(columns) => branch_width.min(columns.saturating_sub(author_width + days_width + 4).max(6)),
+        None => branch_width,
+    };
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "{:<author_width$}  {:>days_width$}  BRANCH", "AUTHOR", "DAYS")?;
+    for (branch, days, author, _) in rows {
+        writeln!(out, "{:<author_width$}  {:>days_width$}  {}", author, days, truncate(branch, branch_width))?;
+    }
+
+    Ok(())
+}
+
+// shells rarely export COLUMNS, so ask the tty; piped output has no width and is never truncated
+fn terminal_width() -> Option<usize> {
+    if let Some(columns) = env::var("COLUMNS").ok().and_then(|columns| columns.parse::<usize>().ok()) {
+        return Some(columns);
+    }
+
+    tty_width()
+}
+
+#[cfg(unix)]
+fn tty_width() -> Option<usize> {
+    let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    // SAFETY: TIOCGWINSZ only writes a winsize into the struct we pass
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_col > 0).then_some(usize::from(size.ws_col))
+}
+
+// without TIOCGWINSZ to ask, assume a classic 80-column console
+#[cfg(not(unix))]
+fn tty_width() -> Option<usize> {
+    Some(80)
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        s.to_string()
+    } else {
+        let kept: String = s.chars().take(width.saturating_sub(1)).collect();
+        format!("{}…", kept)
+    }
+}
+
+fn load_author_map(path: &Path) -> Result<HashMap<String, String>> {
+    let content = fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read author map {}", path.display()))?;
+    Ok(parse_author_map(&content))
+}
+
+fn parse_author_map(content: &str) -> HashMap<String, String> {
+    content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (from, to) = line.split_once('=')?;
+            Some((from.trim().to_string(), to.trim().to_string()))
+        })
+        .collect()
+}
+
+fn apply_author_map(branches: &mut [(String, i64, String, String)], author_map: &HashMap<String, String>) {
+    for (_, _, author, _) in branches.iter_mut() {
+        if let Some(canonical) = author_map.get(author.as_str()) {
+            *author = canonical.clone();
+        }
+    }
 }
 
-fn generate_yaml(branches: &[(String, i64, String)]) -> Result<()> {
+fn group_by_author(branches: &[(String, i64, String, String)]) -> Vec<(String, AuthorBranches)> {
     let mut authors_dict: HashMap<String, AuthorBranches> = HashMap::new();
 
-    for (branch, days, author) in branches {
+    for (branch, days, author, _) in branches {
         authors_dict
             .entry(author.clone())
             .or_insert_with(|| AuthorBranches { branches: vec![], count: 0 })
@@ -93,9 +729,454 @@ fn generate_yaml(branches: &[(String, i64, String)]) -> Result<()> {
         authors_dict.get_mut(author).unwrap().count += 1;
     }
 
+    // stalest author first; ties fall back to the name so reports diff cleanly between runs
+    let mut authors: Vec<(String, AuthorBranches)> = authors_dict.into_iter().collect();
+    authors.sort_by(|(a1, b1), (a2, b2)| b2.max_days().cmp(&b1.max_days()).then_with(|| a1.cmp(a2)));
+    authors
+}
+
+// every bucket is listed, empty ones included, so reports line up between runs
+fn group_by_bucket(branches: &[(String, i64, String, String)], edges: &[i64]) -> Vec<(String, AuthorBranches)> {
+    (0..=edges.len())
+        .map(|i| {
+            let lower = i.checked_sub(1).map(|j| edges[j]);
+            let upper = edges.get(i).copied();
+            let label = match (lower, upper) {
+                (None, Some(upper)) => format!("<{} days", upper),
+                (Some(lower), Some(upper)) => format!("{}-{} days", lower, upper - 1),
+                (Some(lower), None) => format!("{}+ days", lower),
+                (None, None) => "all".to_string(),
+            };
+            let in_bucket: Vec<HashMap<String, i64>> = branches.iter()
+                .filter(|(_, days, _, _)| lower.is_none_or(|lower| *days >= lower) && upper.is_none_or(|upper| *days < upper))
+                .map(|(branch, days, _, _)| HashMap::from([(branch.clone(), *days)]))
+                .collect();
+            (label, AuthorBranches { count: in_bucket.len(), branches: in_bucket })
+        })
+        .collect()
+}
+
+fn generate_buckets(branches: &[(String, i64, String, String)], edges: &[i64]) -> Result<()> {
+    let mut buckets = serde_yaml::Mapping::new();
+    for (label, bucket) in group_by_bucket(branches, edges) {
+        buckets.insert(label.into(), serde_yaml::to_value(bucket)?);
+    }
+    let yaml_data = serde_yaml::to_string(&buckets).wrap_err("Failed to serialize data to YAML")?;
+    io::stdout().write_all(yaml_data.as_bytes()).wrap_err("Failed to write YAML to stdout")?;
+
+    Ok(())
+}
+
+fn generate_yaml(branches: &[(String, i64, String, String)]) -> Result<()> {
+    let mut authors_dict = serde_yaml::Mapping::new();
+    for (author, author_branches) in group_by_author(branches) {
+        authors_dict.insert(author.into(), serde_yaml::to_value(author_branches)?);
+    }
     let yaml_data = serde_yaml::to_string(&authors_dict).wrap_err("Failed to serialize data to YAML")?;
     io::stdout().write_all(yaml_data.as_bytes()).wrap_err("Failed to write YAML to stdout")?;
 
     Ok(())
 }
 
+fn summarize(branches: &[(String, i64, String, String)], remote: &str, days: i64, generated_at: i64) -> Value {
+    let authors: Vec<Value> = group_by_author(branches).into_iter()
+        .map(|(author, author_branches)| json!({
+            "author": author,
+            "count": author_branches.count,
+            "max_days": author_branches.max_days(),
+        }))
+        .collect();
+
+    // ties go to the first branch in name order so the summary is stable between runs
+    let oldest = branches.iter()
+        .min_by(|(b1, d1, _, _), (b2, d2, _, _)| d2.cmp(d1).then_with(|| b1.cmp(b2)))
+        .map(|(branch, days, author, _)| json!({ "branch": branch, "days": days, "author": author }));
+
+    json!({
+        "generated_at": DateTime::from_timestamp(generated_at, 0).map(|t| t.to_rfc3339()),
+        "remote": remote,
+        "days": days,
+        "total": branches.len(),
+        "oldest": oldest,
+        "authors": authors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_704_067_200; // 2024-01-01
+
+    #[test]
+    fn test_parse_branches_local_and_remote() {
+        let output = "\
+1701388800 refs/heads/origin-fix Jane Doe
+1698796800 refs/heads/feature/local Jane Doe
+1696118400 refs/remotes/origin/feature/pushed John Smith
+1696118400 refs/remotes/origin/HEAD John Smith
+";
+        let branches = parse_branches(output, 30, NOW);
+        assert_eq!(branches, vec![
+            ("origin-fix".to_string(), 31, "Jane Doe".to_string(), "refs/heads/origin-fix".to_string()),
+            ("feature/local".to_string(), 61, "Jane Doe".to_string(), "refs/heads/feature/local".to_string()),
+            ("feature/pushed".to_string(), 92, "John Smith".to_string(), "refs/remotes/origin/feature/pushed".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_branches_dedupes_local_and_remote() {
+        let output = "\
+1698796800 refs/heads/shared Jane Doe
+1696118400 refs/remotes/origin/shared Jane Doe
+";
+        let branches = parse_branches(output, 30, NOW);
+        assert_eq!(branches, vec![("shared".to_string(), 61, "Jane Doe".to_string(), "refs/heads/shared".to_string())]);
+    }
+
+    #[test]
+    fn test_deletion_plan_only_stale_remote_tips() {
+        // a stale local feature whose origin/feature has fresh commits
+        let local = "1672531200 refs/heads/feature Jane Doe\n";
+        let branches = parse_branches(local, 30, NOW);
+        assert_eq!(branches.len(), 1);
+        assert!(deletion_plan(&branches, "origin", Some("main")).is_empty());
+
+        let all_refs = "\
+1704067200 refs/remotes/origin/feature Jane Doe
+1672531200 refs/heads/feature Jane Doe
+1672531200 refs/heads/local-only Jane Doe
+1672531200 refs/remotes/upstream/gone John Smith
+1672531200 refs/remotes/origin/old John Smith
+1672531200 refs/remotes/origin/main John Smith
+";
+        let branches = parse_branches(all_refs, 30, NOW);
+        let planned: Vec<&str> = deletion_plan(&branches, "origin", Some("main")).into_iter()
+            .map(|(branch, _, _, _)| branch.as_str())
+            .collect();
+        assert_eq!(planned, vec!["old"]);
+    }
+
+    #[test]
+    fn test_delete_script_skips_local_branches() {
+        let output = "\
+1672531200 refs/heads/feature Jane Doe
+1672531200 refs/remotes/origin/old John Smith
+";
+        let branches = parse_branches(output, 30, NOW);
+        let path = std::env::temp_dir().join(format!("stale-branches-script-{}.sh", std::process::id()));
+        write_delete_script(&path, "origin", &deletion_plan(&branches, "origin", None), 30).unwrap();
+        let script = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(script.contains("git push origin --delete 'old'"));
+        assert!(!script.contains("'feature'"));
+    }
+
+    #[test]
+    fn test_apply_mailmap() {
+        let repo_dir = std::env::temp_dir().join(format!("stale-branches-mailmap-{}", std::process::id()));
+        fs::create_dir_all(&repo_dir).unwrap();
+        let init = SysCommand::new("git").args(["init", "-q"]).current_dir(&repo_dir).status().unwrap();
+        assert!(init.success());
+        fs::write(repo_dir.join(".mailmap"), "Jane Doe <jane@example.com> <jdoe@old.example.com>\n").unwrap();
+
+        let mut branches = vec![
+            ("a".to_string(), 40, "jdoe <jdoe@old.example.com>".to_string(), "refs/remotes/origin/a".to_string()),
+            ("b".to_string(), 50, "Jane Doe <jane@example.com>".to_string(), "refs/remotes/origin/b".to_string()),
+            ("c".to_string(), 60, "John Smith <john@example.com>".to_string(), "refs/remotes/origin/c".to_string()),
+        ];
+        apply_mailmap(&repo_dir, &mut branches).unwrap();
+        fs::remove_dir_all(&repo_dir).unwrap();
+
+        let authors: Vec<&str> = branches.iter().map(|(_, _, author, _)| author.as_str()).collect();
+        assert_eq!(authors, vec!["Jane Doe", "Jane Doe", "John Smith"]);
+    }
+
+    #[test]
+    fn test_author_map_merges_authors() {
+        let author_map = parse_author_map("# nicknames\nJimmy = James Smith\njsmith=James Smith\n");
+        let mut branches = vec![
+            ("a".to_string(), 40, "Jimmy".to_string(), "refs/remotes/origin/a".to_string()),
+            ("b".to_string(), 50, "jsmith".to_string(), "refs/remotes/origin/b".to_string()),
+            ("c".to_string(), 60, "Jane Doe".to_string(), "refs/remotes/origin/c".to_string()),
+        ];
+        apply_author_map(&mut branches, &author_map);
+
+        let grouped = group_by_author(&branches);
+        assert_eq!(grouped.len(), 2);
+        assert_eq!((grouped[0].0.as_str(), grouped[0].1.count), ("Jane Doe", 1));
+        assert_eq!((grouped[1].0.as_str(), grouped[1].1.count), ("James Smith", 2));
+    }
+
+    #[test]
+    fn test_parse_branches_invalid_utf8() {
+        let output = b"1698796800 refs/heads/caf\xe9 Ren\xe9 Dupont\n1696118400 refs/heads/main Jane Doe\n";
+        let branches = parse_branches(&String::from_utf8_lossy(output), 30, NOW);
+        assert_eq!(branches, vec![
+            ("caf\u{FFFD}".to_string(), 61, "Ren\u{FFFD} Dupont".to_string(), "refs/heads/caf\u{FFFD}".to_string()),
+            ("main".to_string(), 92, "Jane Doe".to_string(), "refs/heads/main".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_branches_ignores_non_unix_dates() {
+        // what %(committerdate) yields under a non-default log.date or locale
+        let output = "\
+Mi 1 Nov 2023 12:00:00 CET refs/heads/localized Jane Doe
+2023-11-01 refs/heads/short Jane Doe
+1696118400 refs/heads/unix John Smith
+";
+        let branches = parse_branches(output, 30, NOW);
+        assert_eq!(branches, vec![("unix".to_string(), 92, "John Smith".to_string(), "refs/heads/unix".to_string())]);
+    }
+
+    #[test]
+    fn test_group_by_author_breaks_ties_by_name() {
+        let branches = vec![
+            ("a".to_string(), 90, "Zoe".to_string(), "refs/remotes/origin/a".to_string()),
+            ("b".to_string(), 90, "Adam".to_string(), "refs/remotes/origin/b".to_string()),
+            ("c".to_string(), 120, "Mia".to_string(), "refs/remotes/origin/c".to_string()),
+            ("d".to_string(), 40, "Mia".to_string(), "refs/remotes/origin/d".to_string()),
+            ("e".to_string(), 90, "Liam".to_string(), "refs/remotes/origin/e".to_string()),
+        ];
+
+        for _ in 0..5 {
+            let authors: Vec<String> = group_by_author(&branches).into_iter().map(|(author, _)| author).collect();
+            assert_eq!(authors, vec!["Mia", "Adam", "Liam", "Zoe"]);
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let branches = vec![
+            ("a".to_string(), 40, "Jane Doe".to_string(), "refs/remotes/origin/a".to_string()),
+            ("b".to_string(), 120, "John Smith".to_string(), "refs/remotes/origin/b".to_string()),
+            ("c".to_string(), 120, "Jane Doe".to_string(), "refs/remotes/origin/c".to_string()),
+        ];
+        let summary = summarize(&branches, "origin", 30, NOW);
+
+        assert_eq!(summary["generated_at"], "2024-01-01T00:00:00+00:00");
+        assert_eq!(summary["total"], 3);
+        assert_eq!(summary["oldest"], json!({ "branch": "b", "days": 120, "author": "John Smith" }));
+        assert_eq!(summary["authors"], json!([
+            { "author": "Jane Doe", "count": 2, "max_days": 120 },
+            { "author": "John Smith", "count": 1, "max_days": 120 },
+        ]));
+    }
+
+    #[test]
+    fn test_merged_branches_against_develop() {
+        let repo_dir = std::env::temp_dir().join(format!("stale-branches-develop-{}", std::process::id()));
+        fs::create_dir_all(&repo_dir).unwrap();
+        let git = |args: &[&str]| {
+            let status = SysCommand::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .current_dir(&repo_dir)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q", "-b", "main"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "init"]);
+        git(&["checkout", "-q", "-b", "develop"]);
+        git(&["checkout", "-q", "-b", "feature/merged"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "merged"]);
+        git(&["checkout", "-q", "develop"]);
+        git(&["merge", "-q", "--ff-only", "feature/merged"]);
+        git(&["checkout", "-q", "-b", "feature/open"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "open"]);
+
+        let base = resolve_base(&repo_dir, "origin", Some("develop")).unwrap();
+        let merged = merged_branches(&repo_dir, &base, &["refs/heads".to_string()]).unwrap();
+        let missing = resolve_base(&repo_dir, "origin", Some("nope"));
+        fs::remove_dir_all(&repo_dir).unwrap();
+
+        assert_eq!(base, "develop");
+        assert_eq!(merged, HashSet::from(["main".to_string(), "feature/merged".to_string()]));
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_libgit2_matches_for_each_ref() {
+        let repo_dir = std::env::temp_dir().join(format!("stale-branches-libgit2-{}", std::process::id()));
+        fs::create_dir_all(&repo_dir).unwrap();
+        let git = |args: &[&str], date: &str| {
+            let output = SysCommand::new("git")
+                .args(["-c", "user.name=Jane Doe", "-c", "user.email=jane@example.com"])
+                .args(args)
+                .env("GIT_COMMITTER_DATE", date)
+                .current_dir(&repo_dir)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        };
+        git(&["init", "-q", "-b", "main"], "");
+        git(&["commit", "-q", "--allow-empty", "-m", "old"], "2023-10-01T00:00:00Z");
+        git(&["branch", "feature/old"], "");
+        git(&["commit", "-q", "--allow-empty", "-m", "new"], "2023-12-01T00:00:00Z");
+        let output = git(&["for-each-ref", "--sort=-committerdate", "--format=%(committerdate:unix) %(refname) %(committername)", "refs/heads"], "");
+
+        let expected = parse_branches(&output, 30, NOW);
+        let actual = get_stale_branches_libgit2(&repo_dir, 30, &["refs/heads".to_string()], Some(NOW)).unwrap();
+        fs::remove_dir_all(&repo_dir).unwrap();
+
+        assert_eq!(actual, vec![
+            ("main".to_string(), 31, "Jane Doe".to_string(), "refs/heads/main".to_string()),
+            ("feature/old".to_string(), 92, "Jane Doe".to_string(), "refs/heads/feature/old".to_string()),
+        ]);
+        assert_eq!(actual, expected);
+    }
+
+    // timing harness for --libgit2 against a large fixture; run with
+    // cargo test -p stale-branches --release -- --ignored --nocapture bench_
+    #[test]
+    #[ignore]
+    fn bench_libgit2_vs_for_each_ref() {
+        const COMMITS: usize = 50;
+        const REFS: usize = 20_000;
+        const RUNS: u32 = 5;
+
+        let repo_dir = std::env::temp_dir().join(format!("stale-branches-bench-{}", std::process::id()));
+        fs::create_dir_all(&repo_dir).unwrap();
+        let git = |args: &[&str], stdin: Option<&str>| {
+            let mut child = SysCommand::new("git")
+                .args(["-c", "user.name=Jane Doe", "-c", "user.email=jane@example.com"])
+                .args(args)
+                .current_dir(&repo_dir)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            child.stdin.take().unwrap().write_all(stdin.unwrap_or_default().as_bytes()).unwrap();
+            let output = child.wait_with_output().unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        git(&["init", "-q", "-b", "main"], None);
+        let commits: Vec<String> = (0..COMMITS).map(|i| {
+            git(&["commit", "-q", "--allow-empty", "-m", &format!("commit {}", i)], None);
+            git(&["rev-parse", "HEAD"], None)
+        }).collect();
+        let updates: String = (0..REFS)
+            .map(|i| format!("create refs/remotes/origin/branch-{} {}\n", i, commits[i % COMMITS]))
+            .collect();
+        git(&["update-ref", "--stdin"], Some(&updates));
+
+        let refs = ["refs/remotes/origin".to_string()];
+        let time = |label: &str, scan: &dyn Fn() -> Vec<(String, i64, String, String)>| {
+            let start = std::time::Instant::now();
+            let mut found = 0;
+            for _ in 0..RUNS {
+                found = scan().len();
+            }
+            eprintln!("{:>14}: {:?} per scan of {} refs", label, start.elapsed() / RUNS, found);
+            found
+        };
+        let for_each_ref = time("for-each-ref", &|| get_stale_branches(&repo_dir, 0, &refs, None).unwrap());
+        let libgit2 = time("libgit2", &|| get_stale_branches_libgit2(&repo_dir, 0, &refs, None).unwrap());
+        fs::remove_dir_all(&repo_dir).unwrap();
+
+        assert_eq!((for_each_ref, libgit2), (REFS, REFS));
+    }
+
+    #[test]
+    fn test_repo_config_overrides_cli() {
+        let repo_dir = std::env::temp_dir().join(format!("stale-branches-config-{}", std::process::id()));
+        fs::create_dir_all(&repo_dir).unwrap();
+        let init = SysCommand::new("git").args(["init", "-q"]).current_dir(&repo_dir).status().unwrap();
+        assert!(init.success());
+        fs::write(repo_dir.join(REPO_CONFIG), "\
+remote = \"upstream\"
+
+[stale-branches]
+exclude-bots = true
+bots = [\"ci-*\"]
+author-map = \"authors.txt\"
+").unwrap();
+
+        let mut args = Cli::parse_from(["stale-branches", "30", "--remote", "fork", "--bot", "deploy*"]);
+        apply_repo_config(&mut args, load_repo_config(&repo_dir).unwrap());
+        let repo_root = Repository::discover(&repo_dir).unwrap().workdir().unwrap().to_path_buf();
+        fs::remove_dir_all(&repo_dir).unwrap();
+
+        assert_eq!(args.remote, "upstream");
+        assert!(args.exclude_bots);
+        assert_eq!(args.bots, vec!["deploy*", "ci-*"]);
+        assert_eq!(args.author_map, Some(repo_root.join("authors.txt")));
+        assert_eq!(args.base, None);
+    }
+
+    #[test]
+    fn test_missing_program_error() {
+        let program = "git-tools-no-such-program";
+        let err = ensure_installed(program).unwrap_err();
+        assert_eq!(err.to_string(), format!("{} not found on PATH; please install {}", program, program));
+
+        let err = wait_bounded(&mut SysCommand::new(program), Some(Duration::from_secs(1))).unwrap_err();
+        assert_eq!(err.to_string(), format!("{} not found on PATH; please install {}", program, program));
+    }
+
+    #[test]
+    fn test_group_by_bucket() {
+        let branches = vec![
+            ("a".to_string(), 45, "Jane Doe".to_string(), "refs/remotes/origin/a".to_string()),
+            ("b".to_string(), 90, "Jane Doe".to_string(), "refs/remotes/origin/b".to_string()),
+            ("c".to_string(), 400, "John Smith".to_string(), "refs/remotes/origin/c".to_string()),
+        ];
+        let buckets: Vec<(String, usize)> = group_by_bucket(&branches, &[90, 180, 365]).into_iter()
+            .map(|(label, bucket)| (label, bucket.count))
+            .collect();
+        assert_eq!(buckets, vec![
+            ("<90 days".to_string(), 1),
+            ("90-179 days".to_string(), 1),
+            ("180-364 days".to_string(), 0),
+            ("365+ days".to_string(), 1),
+        ]);
+        assert!(check_bucket_edges(&[180, 90]).is_err());
+    }
+
+    #[test]
+    fn test_pick_remote() {
+        let remotes = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(pick_remote("upstream", &remotes(&["origin", "upstream"])).unwrap(), "upstream");
+        assert_eq!(pick_remote("origin", &remotes(&["fork"])).unwrap(), "fork");
+
+        let err = pick_remote("origin", &remotes(&["fork", "upstream"])).unwrap_err();
+        assert_eq!(err.to_string(), "Remote 'origin' not found; available: fork, upstream");
+        assert!(pick_remote("origin", &[]).is_err());
+    }
+
+    #[test]
+    fn test_is_bot_default_patterns() {
+        assert!(is_bot("dependabot[bot]", &DEFAULT_BOT_PATTERNS));
+        assert!(is_bot("GitHub-Actions[bot]", &DEFAULT_BOT_PATTERNS));
+        assert!(is_bot("Dependabot Preview", &DEFAULT_BOT_PATTERNS));
+        assert!(is_bot("renovate-bot", &DEFAULT_BOT_PATTERNS));
+        assert!(!is_bot("Jane Doe", &DEFAULT_BOT_PATTERNS));
+        assert!(!is_bot("Abbot Costello", &DEFAULT_BOT_PATTERNS));
+
+        assert!(is_bot("ci-robot", &["ci-*"]));
+        assert!(is_bot("deploy", &["Deploy"]));
+        assert!(!is_bot("deployer", &["deploy"]));
+    }
+
+    #[test]
+    fn test_matches_author() {
+        let wanted = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
+        assert!(matches_author("Jane Doe", &wanted(&["jane"]), false));
+        assert!(matches_author("Jane Doe", &wanted(&["bob", "DOE"]), false));
+        assert!(!matches_author("Jane Doe", &wanted(&["john"]), false));
+        // the report carries author names only, emails are stripped before filtering
+        assert!(!matches_author("Jane Doe", &wanted(&["jane@example.com"]), false));
+
+        assert!(matches_author("Jane Doe", &wanted(&["Jane Doe"]), true));
+        assert!(!matches_author("Jane Doe", &wanted(&["jane doe"]), true));
+        assert!(!matches_author("Jane Doe", &wanted(&["Jane"]), true));
+    }
+}